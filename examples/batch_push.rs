@@ -1,17 +1,15 @@
-use bark_rs::{Level, SyncBarkClient};
+use bark_rs::{BarkConfig, Level};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // 创建同步客户端
-    let client = SyncBarkClient::new("https://api.day.app");
+    // 服务器地址和设备密钥取自环境变量或配置文件，例如：
+    // BARK_KEY=key1,key2,key3 cargo run --example batch_push
+    let profile = BarkConfig::load()?.profile(None)?;
+    let client = profile.sync_builder()?.build()?;
 
     // 批量推送到多个设备
     let response = client
         .message()
-        .device_keys(vec![
-            "QJ48vPutCAsPW2B6pE2A3a".to_string(),
-            "device_key_2".to_string(),
-            "device_key_3".to_string(),
-        ])
+        .device_keys(profile.device_keys()?)
         .title("批量推送通知")
         .body("这是一个发送给多个设备的批量消息")
         .level(Level::TimeSensitive)
@@ -1,4 +1,4 @@
-use bark_rs::{BarkError, BarkMessage, SyncBarkClient};
+use bark_rs::{ApiErrorKind, BarkError, BarkMessage, SyncBarkClient};
 
 fn main() {
     println!("🚨 演示错误处理");
@@ -47,6 +47,48 @@ fn main() {
         Err(BarkError::InvalidUrl) => {
             println!("❌ 无效URL");
         }
+        Err(BarkError::EncryptionError(e)) => {
+            println!("❌ 加密错误: {}", e);
+        }
+        Err(BarkError::TransportError(e)) => {
+            println!("❌ 传输层错误: {}", e);
+        }
+        Err(BarkError::Api(e)) if e.kind == ApiErrorKind::InvalidDeviceKey => {
+            println!("❌ 设备密钥无效: {}", e.message);
+        }
+        Err(BarkError::Api(e)) => {
+            println!("❌ 服务器返回错误: {}", e);
+        }
+        Err(BarkError::InvalidResponse { status, body }) => {
+            println!("❌ 无法解析的响应 (HTTP {}): {}", status, body);
+        }
+        Err(BarkError::RetriesExhausted {
+            attempts,
+            last_error,
+        }) => {
+            println!("❌ 重试 {} 次后仍然失败: {}", attempts, last_error);
+        }
+        Err(BarkError::InvalidConfig(e)) => {
+            println!("❌ 配置错误: {}", e);
+        }
+        Err(BarkError::Unauthorized) => {
+            println!("❌ 服务器拒绝了认证凭据");
+        }
+        Err(BarkError::Validation(e)) => {
+            println!("❌ 消息校验失败: {}", e);
+        }
+        Err(BarkError::IoError(e)) => {
+            println!("❌ 文件读写错误: {}", e);
+        }
+        Err(BarkError::Suppressed(e)) => {
+            println!("⏸️ 消息被抑制（限流或重复）: {}", e);
+        }
+        Err(BarkError::UnknownRecipient(name)) => {
+            println!("❌ 未知的设备或设备组: {}", name);
+        }
+        Err(BarkError::PartialDelivery { delivered, error }) => {
+            println!("⚠️ {:?} 已收到推送，其余设备发送失败: {}", delivered, error);
+        }
     }
 
     println!("🎉 错误处理演示完成！");
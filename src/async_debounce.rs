@@ -0,0 +1,191 @@
+//! 异步防抖推送模块
+//!
+//! [`Debouncer`](crate::Debouncer) 的异步版本：在 tokio 任务中使用 [`AsyncBarkClient`]
+//! 发送安静期已过的消息，合并规则和关闭时的行为与同步版本相同。
+//!
+//! # 示例
+//!
+//! ```rust,no_run
+//! use bark_rs::{AsyncBarkClient, AsyncDebouncer, BarkMessage};
+//! use std::time::Duration;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let client = AsyncBarkClient::with_device_key("https://api.day.app", "your_key");
+//!     let debouncer = AsyncDebouncer::builder(client)
+//!         .quiet_period(Duration::from_secs(5))
+//!         .spawn();
+//!
+//!     let message = BarkMessage::builder().id("deploy").body("部署中").build();
+//!     debouncer.submit(message).ok();
+//!
+//!     let report = debouncer.shutdown().await;
+//!     println!("发送 {} 条", report.delivered);
+//! }
+//! ```
+//!
+//! # 注意
+//!
+//! 这个模块只在启用 `async` feature 时才可用。
+
+#![cfg(feature = "async")]
+
+use crate::debounce::{DEFAULT_QUIET_PERIOD, DebounceReport, DebounceState, Next, debounce_key};
+use crate::worker::{EnqueueError, FailureHandler};
+use crate::{AsyncBarkClient, BarkError, BarkMessage};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+/// 异步防抖任务的共享状态
+struct Shared {
+    state: Mutex<DebounceState>,
+    changed: Notify,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, DebounceState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// 在 tokio 任务中合并并发送快速更新的消息
+///
+/// 通过 [`AsyncDebouncer::builder`] 创建，必须在 tokio 运行时中启动。
+/// 不调用 [`shutdown`](Self::shutdown) 直接丢弃时，等待中的消息仍会在后台任务中立即发送。
+pub struct AsyncDebouncer {
+    shared: Arc<Shared>,
+    task: Option<JoinHandle<()>>,
+    quiet_period: Duration,
+    client: AsyncBarkClient,
+}
+
+/// [`AsyncDebouncer`] 构建器
+pub struct AsyncDebouncerBuilder {
+    client: AsyncBarkClient,
+    quiet_period: Duration,
+    on_failure: Option<FailureHandler>,
+}
+
+impl AsyncDebouncer {
+    /// 创建防抖任务构建器
+    ///
+    /// 默认安静期为 [`DEFAULT_QUIET_PERIOD`](crate::DEFAULT_QUIET_PERIOD)。
+    pub fn builder(client: AsyncBarkClient) -> AsyncDebouncerBuilder {
+        AsyncDebouncerBuilder {
+            client,
+            quiet_period: DEFAULT_QUIET_PERIOD,
+            on_failure: None,
+        }
+    }
+
+    /// 提交一条消息
+    ///
+    /// 不会等待，可以在同步代码中调用。安静期内提交了同一个键的新消息时，这条消息被替换。
+    /// 防抖任务关闭后返回 [`EnqueueError::Closed`]。
+    pub fn submit(&self, message: BarkMessage) -> Result<(), EnqueueError> {
+        // 收件人无法解析时按原始消息合并，发送时的错误交给 on_failure 处理
+        let targets = self
+            .client
+            .targets(&message)
+            .unwrap_or_else(|_| vec![message.clone().into()]);
+        let key = debounce_key(&targets, self.client.default_device_key.as_deref());
+        let due = Instant::now() + self.quiet_period;
+        self.shared.lock().submit(key, message, due)?;
+        self.shared.changed.notify_one();
+        Ok(())
+    }
+
+    /// 等待发送的消息数量
+    pub fn len(&self) -> usize {
+        self.shared.lock().len()
+    }
+
+    /// 是否没有等待发送的消息
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 关闭防抖任务
+    ///
+    /// 不再接受新消息，立即发送所有等待中的消息，等待发送完成后返回结果。
+    pub async fn shutdown(mut self) -> DebounceReport {
+        self.shared.lock().close();
+        self.shared.changed.notify_one();
+        if let Some(task) = self.task.take() {
+            let _ = task.await;
+        }
+        self.shared.lock().report()
+    }
+}
+
+impl Drop for AsyncDebouncer {
+    fn drop(&mut self) {
+        self.shared.lock().close();
+        self.shared.changed.notify_one();
+    }
+}
+
+impl AsyncDebouncerBuilder {
+    /// 设置安静期
+    pub fn quiet_period(mut self, quiet_period: Duration) -> Self {
+        self.quiet_period = quiet_period;
+        self
+    }
+
+    /// 设置发送失败时的回调
+    ///
+    /// 后台发送的错误无法返回给调用方，可以在这里记录日志。
+    pub fn on_failure<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&BarkMessage, &BarkError) + Send + Sync + 'static,
+    {
+        self.on_failure = Some(Arc::new(handler));
+        self
+    }
+
+    /// 在当前 tokio 运行时中启动后台任务
+    pub fn spawn(self) -> AsyncDebouncer {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(DebounceState::default()),
+            changed: Notify::new(),
+        });
+        let task = tokio::spawn(run(shared.clone(), self.client.clone(), self.on_failure));
+
+        AsyncDebouncer {
+            shared,
+            task: Some(task),
+            quiet_period: self.quiet_period,
+            client: self.client,
+        }
+    }
+}
+
+/// 后台任务的主循环
+async fn run(shared: Arc<Shared>, client: AsyncBarkClient, on_failure: Option<FailureHandler>) {
+    loop {
+        let next = shared.lock().next(Instant::now());
+        let message = match next {
+            Next::Message(message) => message,
+            Next::Wait(Some(due)) => {
+                let due = tokio::time::Instant::from_std(due);
+                let _ = tokio::time::timeout_at(due, shared.changed.notified()).await;
+                continue;
+            }
+            Next::Wait(None) => {
+                shared.changed.notified().await;
+                continue;
+            }
+            Next::Exit => return,
+        };
+
+        let result = client.send(&message).await;
+        if let (Err(error), Some(on_failure)) = (&result, &on_failure) {
+            on_failure(&message, error);
+        }
+        shared.lock().record(result.is_ok());
+    }
+}
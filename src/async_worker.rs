@@ -0,0 +1,263 @@
+//! 异步后台推送模块
+//!
+//! [`BarkWorker`](crate::BarkWorker) 的异步版本：在 tokio 任务中使用 [`AsyncBarkClient`] 发送推送，
+//! 队列满时的处理方式和关闭时的行为与同步版本相同。
+//!
+//! # 示例
+//!
+//! ```rust,no_run
+//! use bark_rs::{AsyncBarkClient, AsyncBarkWorker, BarkMessage};
+//! use std::time::Duration;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let client = AsyncBarkClient::with_device_key("https://api.day.app", "your_key");
+//!     let worker = AsyncBarkWorker::builder(client).capacity(1000).spawn();
+//!
+//!     let handle = worker.handle();
+//!     handle.enqueue(BarkMessage::builder().body("订单已创建").build()).await.ok();
+//!
+//!     let report = worker.shutdown(Duration::from_secs(5)).await;
+//!     println!("发送 {} 条", report.delivered);
+//! }
+//! ```
+//!
+//! # 注意
+//!
+//! 这个模块只在启用 `async` feature 时才可用。
+
+#![cfg(feature = "async")]
+
+use crate::worker::{
+    DEFAULT_QUEUE_CAPACITY, EnqueueError, FailureHandler, OverflowPolicy, Pop, Push, QueueState,
+    ShutdownReport,
+};
+use crate::{AsyncBarkClient, BarkError, BarkMessage};
+use std::pin::pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// 异步后台任务的共享状态
+struct Shared {
+    state: Mutex<QueueState>,
+    not_empty: Notify,
+    not_full: Notify,
+    finished: Notify,
+    capacity: usize,
+    policy: OverflowPolicy,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn close(&self, deadline: Option<Instant>) {
+        self.lock().close(deadline.map(Instant::into_std));
+        self.not_empty.notify_one();
+        self.not_full.notify_waiters();
+    }
+}
+
+/// 在 tokio 任务中发送推送
+///
+/// 通过 [`AsyncBarkWorker::builder`] 创建，必须在 tokio 运行时中启动。
+/// [`handle()`](Self::handle) 返回的 [`AsyncWorkerHandle`] 可以廉价地克隆。
+///
+/// 不调用 [`shutdown`](Self::shutdown) 直接丢弃时，队列会被关闭，
+/// 剩余的消息仍会在后台任务中发送完毕。
+pub struct AsyncBarkWorker {
+    shared: Arc<Shared>,
+    task: Option<JoinHandle<()>>,
+}
+
+/// 向异步后台任务提交消息的句柄
+#[derive(Clone)]
+pub struct AsyncWorkerHandle {
+    shared: Arc<Shared>,
+}
+
+/// [`AsyncBarkWorker`] 构建器
+pub struct AsyncBarkWorkerBuilder {
+    client: AsyncBarkClient,
+    capacity: usize,
+    policy: OverflowPolicy,
+    on_failure: Option<FailureHandler>,
+}
+
+impl AsyncBarkWorker {
+    /// 创建后台任务构建器
+    ///
+    /// 默认队列容量为 [`DEFAULT_QUEUE_CAPACITY`](crate::DEFAULT_QUEUE_CAPACITY)，队列已满时等待。
+    pub fn builder(client: AsyncBarkClient) -> AsyncBarkWorkerBuilder {
+        AsyncBarkWorkerBuilder {
+            client,
+            capacity: DEFAULT_QUEUE_CAPACITY,
+            policy: OverflowPolicy::default(),
+            on_failure: None,
+        }
+    }
+
+    /// 获取提交消息的句柄
+    pub fn handle(&self) -> AsyncWorkerHandle {
+        AsyncWorkerHandle {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// 关闭后台任务
+    ///
+    /// 不再接受新消息，并在 `deadline` 内继续发送队列中的消息。到达截止时间后，
+    /// 尚未发送的消息会通过 [`ShutdownReport::unsent`] 返回，正在发送的消息通过
+    /// [`ShutdownReport::in_flight`] 返回。
+    pub async fn shutdown(mut self, deadline: Duration) -> ShutdownReport {
+        let deadline = Instant::now() + deadline;
+        self.shared.close(Some(deadline));
+
+        loop {
+            let mut finished = pin!(self.shared.finished.notified());
+            finished.as_mut().enable();
+            if self.shared.lock().is_finished() {
+                break;
+            }
+            if tokio::time::timeout_at(deadline, finished).await.is_err() {
+                break;
+            }
+        }
+
+        let (finished, report) = {
+            let mut state = self.shared.lock();
+            (state.is_finished(), state.report())
+        };
+
+        // 正在发送的请求可能超过截止时间，此时不再等待后台任务
+        if finished {
+            if let Some(task) = self.task.take() {
+                let _ = task.await;
+            }
+        }
+        report
+    }
+}
+
+impl Drop for AsyncBarkWorker {
+    fn drop(&mut self) {
+        self.shared.close(None);
+    }
+}
+
+impl AsyncWorkerHandle {
+    /// 提交一条消息
+    ///
+    /// 队列已满时按照 [`OverflowPolicy`] 处理：`Block` 会等待队列有空位，
+    /// `DropNewest` 返回 [`EnqueueError::Full`]，`DropOldest` 丢弃最早的消息。
+    /// 后台任务关闭后返回 [`EnqueueError::Closed`]。
+    pub async fn enqueue(&self, message: BarkMessage) -> Result<(), EnqueueError> {
+        let mut message = message;
+        loop {
+            let mut not_full = pin!(self.shared.not_full.notified());
+            not_full.as_mut().enable();
+
+            let result = self
+                .shared
+                .lock()
+                .push(message, self.shared.capacity, self.shared.policy);
+            match result {
+                Push::Queued => {
+                    self.shared.not_empty.notify_one();
+                    return Ok(());
+                }
+                Push::Rejected(error) => return Err(error),
+                Push::Wait(pending) => {
+                    message = pending;
+                    not_full.await;
+                }
+            }
+        }
+    }
+
+    /// 队列中等待发送的消息数量
+    pub fn len(&self) -> usize {
+        self.shared.lock().len()
+    }
+
+    /// 队列是否为空
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl AsyncBarkWorkerBuilder {
+    /// 设置队列容量，最小为 1
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// 设置队列已满时的处理方式
+    pub fn overflow_policy(mut self, policy: OverflowPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// 设置发送失败时的回调
+    ///
+    /// 后台发送的错误无法返回给调用方，可以在这里记录日志。
+    pub fn on_failure<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&BarkMessage, &BarkError) + Send + Sync + 'static,
+    {
+        self.on_failure = Some(Arc::new(handler));
+        self
+    }
+
+    /// 在当前 tokio 运行时中启动后台任务
+    pub fn spawn(self) -> AsyncBarkWorker {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(QueueState::default()),
+            not_empty: Notify::new(),
+            not_full: Notify::new(),
+            finished: Notify::new(),
+            capacity: self.capacity,
+            policy: self.policy,
+        });
+
+        let task = tokio::spawn(run(shared.clone(), self.client, self.on_failure));
+
+        AsyncBarkWorker {
+            shared,
+            task: Some(task),
+        }
+    }
+}
+
+/// 后台任务的主循环
+async fn run(shared: Arc<Shared>, client: AsyncBarkClient, on_failure: Option<FailureHandler>) {
+    loop {
+        let popped = shared.lock().pop();
+        let message = match popped {
+            Pop::Message(message) => message,
+            Pop::Wait => {
+                shared.not_empty.notified().await;
+                continue;
+            }
+            Pop::Exit => {
+                shared.lock().finish();
+                shared.finished.notify_waiters();
+                return;
+            }
+        };
+        shared.not_full.notify_waiters();
+
+        let result = client.send(&message).await;
+        if let (Err(error), Some(on_failure)) = (&result, &on_failure) {
+            on_failure(&message, error);
+        }
+        shared.lock().record(result.is_ok());
+    }
+}
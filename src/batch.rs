@@ -0,0 +1,194 @@
+//! 批量推送模块
+//!
+//! 这个模块提供了向多个设备发送同一条消息的两种方式：
+//!
+//! - [`BatchMode::Server`]：通过 `device_keys` 交给服务器批量推送，设备过多时自动拆分为多个请求
+//! - [`BatchMode::FanOut`]：客户端为每个设备单独发送请求，可以准确知道每个设备的推送结果
+//!
+//! 两种方式都返回 [`BatchReport`]，列出每个设备密钥的推送结果和耗时。
+//!
+//! # 示例
+//!
+//! ```rust,no_run
+//! use bark_rs::{BarkMessage, BatchMode, SyncBarkClient};
+//!
+//! # fn example() -> bark_rs::Result<()> {
+//! let client = SyncBarkClient::new("https://api.day.app")
+//!     .with_batch_mode(BatchMode::FanOut { concurrency: 4 });
+//!
+//! let message = BarkMessage::builder()
+//!     .body("发布完成")
+//!     .device_keys(vec!["key1".to_string(), "key2".to_string()])
+//!     .build();
+//!
+//! let report = client.send_batch(&message)?;
+//! for delivery in report.failed() {
+//!     eprintln!("{} 推送失败: {:?}", delivery.device_key, delivery.result);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{BarkError, BarkMessage, BarkResponse, Result};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 服务器批量推送时单个请求默认包含的最多设备数量
+pub const DEFAULT_CHUNK_SIZE: usize = 100;
+
+/// 批量推送的发送方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchMode {
+    /// 通过 `device_keys` 由服务器批量推送
+    ///
+    /// 每个请求最多包含 `chunk_size` 个设备密钥，超出时拆分为多个请求依次发送。
+    /// 同一个请求中的设备共享同一个推送结果。
+    Server {
+        /// 单个请求包含的最多设备数量
+        chunk_size: usize,
+    },
+
+    /// 客户端为每个设备单独发送请求
+    ///
+    /// 最多同时发送 `concurrency` 个请求。
+    FanOut {
+        /// 最大并发请求数量
+        concurrency: usize,
+    },
+}
+
+impl Default for BatchMode {
+    /// 默认由服务器批量推送，每个请求最多包含 [`DEFAULT_CHUNK_SIZE`] 个设备
+    fn default() -> Self {
+        BatchMode::Server {
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+}
+
+/// 单个设备的推送结果
+#[derive(Debug, Clone)]
+pub struct Delivery {
+    /// 设备密钥
+    pub device_key: String,
+
+    /// 推送结果
+    ///
+    /// 服务器批量推送时，同一个请求中的设备共享同一个错误，因此错误以 `Arc` 包装。
+    pub result: std::result::Result<BarkResponse, Arc<BarkError>>,
+
+    /// 发送这个设备的推送所用的时间（包括重试）
+    pub latency: Duration,
+}
+
+impl Delivery {
+    /// 是否推送成功
+    pub fn is_success(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+/// 批量推送的结果报告
+///
+/// 按照设备密钥的原始顺序列出每个设备的推送结果。
+#[derive(Debug, Clone, Default)]
+pub struct BatchReport {
+    /// 每个设备的推送结果
+    pub deliveries: Vec<Delivery>,
+}
+
+impl BatchReport {
+    /// 推送成功的设备
+    pub fn succeeded(&self) -> impl Iterator<Item = &Delivery> {
+        self.deliveries
+            .iter()
+            .filter(|delivery| delivery.is_success())
+    }
+
+    /// 推送失败的设备
+    pub fn failed(&self) -> impl Iterator<Item = &Delivery> {
+        self.deliveries
+            .iter()
+            .filter(|delivery| !delivery.is_success())
+    }
+
+    /// 是否所有设备都推送成功
+    pub fn is_success(&self) -> bool {
+        self.deliveries.iter().all(Delivery::is_success)
+    }
+
+    /// 查找指定设备的推送结果
+    pub fn get(&self, device_key: &str) -> Option<&Delivery> {
+        self.deliveries
+            .iter()
+            .find(|delivery| delivery.device_key == device_key)
+    }
+}
+
+/// 获取批量推送的目标设备
+///
+/// 优先使用消息中的 `device_keys`，其次是 `device_key`，最后是客户端的默认设备密钥。
+pub(crate) fn target_keys(
+    message: &BarkMessage,
+    default_device_key: Option<&str>,
+) -> Result<Vec<String>> {
+    let keys = match (&message.device_keys, &message.device_key) {
+        (Some(keys), _) => keys.clone(),
+        (None, Some(key)) => vec![key.clone()],
+        (None, None) => default_device_key.map(str::to_string).into_iter().collect(),
+    };
+
+    if keys.is_empty() {
+        Err(BarkError::MissingDeviceKey)
+    } else {
+        Ok(keys)
+    }
+}
+
+/// 发送给单个设备的消息
+pub(crate) fn single(message: &BarkMessage, device_key: &str) -> BarkMessage {
+    BarkMessage {
+        device_key: Some(device_key.to_string()),
+        device_keys: None,
+        ..message.clone()
+    }
+}
+
+/// 发送给一组设备的消息
+pub(crate) fn chunk(message: &BarkMessage, device_keys: &[String]) -> BarkMessage {
+    BarkMessage {
+        device_key: None,
+        device_keys: Some(device_keys.to_vec()),
+        ..message.clone()
+    }
+}
+
+/// 生成单个设备的推送结果
+pub(crate) fn delivery(
+    device_key: &str,
+    result: Result<BarkResponse>,
+    latency: Duration,
+) -> Delivery {
+    Delivery {
+        device_key: device_key.to_string(),
+        result: result.map_err(Arc::new),
+        latency,
+    }
+}
+
+/// 为同一个请求中的所有设备生成推送结果
+pub(crate) fn deliveries(
+    device_keys: &[String],
+    result: Result<BarkResponse>,
+    latency: Duration,
+) -> Vec<Delivery> {
+    let result = result.map_err(Arc::new);
+    device_keys
+        .iter()
+        .map(|device_key| Delivery {
+            device_key: device_key.clone(),
+            result: result.clone(),
+            latency,
+        })
+        .collect()
+}
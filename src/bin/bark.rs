@@ -0,0 +1,863 @@
+//! `bark` 命令行工具
+//!
+//! 在 shell 脚本、cron 和 systemd 单元中发送 Bark 推送：
+//!
+//! ```text
+//! $ export BARK_KEY=your_key
+//! $ bark -t "备份完成" "共 42 GB"
+//! $ df -h | bark -t "磁盘使用情况" --group ops
+//! $ bark exec --only-on-failure -- pg_dump -f backup.sql mydb
+//! ```
+//!
+//! 服务器地址、设备密钥和默认的推送参数依次取自命令行参数、环境变量（`BARK_SERVER`、`BARK_KEY` 等）
+//! 和配置文件（`--config`、`BARK_CONFIG` 或 `~/.config/bark/config.toml`），
+//! 配置文件的格式参见 [`BarkConfig`]，`--profile` 选择其中的 profile，
+//! `--to` 按名称选择配置文件中 `[devices]`、`[groups]` 定义的设备或设备组。
+//! 省略推送内容时从标准输入读取。失败时的退出码参见 [`BarkError::exit_code`]，
+//! 命令行用法错误的退出码为 64。`bark exec` 使用子进程的退出码。
+//!
+//! 这个程序只在启用 `cli` feature 时才会构建。
+
+use bark_rs::{
+    BarkConfig, BarkEncryptor, BarkError, BarkMessage, BarkMessageBuilder, EncryptionMode,
+    ExecOptions, Level, Profile, SyncBarkClient, WireFormat, run_and_notify,
+};
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use serde_json::Value;
+use std::io::{IsTerminal, Read};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+/// 命令行用法错误的退出码（`sysexits.h` 中的 `EX_USAGE`）
+const EXIT_USAGE: u8 = 64;
+
+/// 向 Bark 发送推送通知
+#[derive(Parser)]
+#[command(name = "bark", version, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    #[command(flatten)]
+    message: MessageArgs,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// 运行命令，结束后推送退出状态、运行时间和最后若干行输出
+    Exec(ExecArgs),
+}
+
+/// `bark exec` 的参数
+#[derive(Args)]
+struct ExecArgs {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    /// 只在命令失败时推送
+    #[arg(long)]
+    only_on_failure: bool,
+
+    /// 只在命令运行超过指定秒数时推送，失败的命令不受影响
+    #[arg(long, value_name = "SECONDS")]
+    min_duration: Option<f64>,
+
+    /// 命令成功时的标题模板 [默认: "{command} succeeded"]
+    #[arg(long, value_name = "TEMPLATE")]
+    success_title: Option<String>,
+
+    /// 命令成功时的内容模板
+    #[arg(long, value_name = "TEMPLATE")]
+    success_body: Option<String>,
+
+    /// 命令失败时的标题模板 [默认: "{command} failed"]
+    #[arg(long, value_name = "TEMPLATE")]
+    failure_title: Option<String>,
+
+    /// 命令失败时的内容模板
+    #[arg(long, value_name = "TEMPLATE")]
+    failure_body: Option<String>,
+
+    /// 命令成功时的推送级别 [默认: active]
+    #[arg(long)]
+    success_level: Option<Level>,
+
+    /// 命令失败时的推送级别 [默认: time-sensitive]
+    #[arg(long)]
+    failure_level: Option<Level>,
+
+    /// 推送中保留的输出行数
+    #[arg(long, value_name = "N", default_value_t = 10)]
+    tail_lines: usize,
+
+    /// 消息分组
+    #[arg(short, long)]
+    group: Option<String>,
+
+    /// 铃声名称
+    #[arg(long)]
+    sound: Option<String>,
+
+    /// 点击推送时跳转的 URL
+    #[arg(short, long)]
+    url: Option<String>,
+
+    /// 设备注册表中的设备或设备组，可以重复使用，指定时不使用 --key 和配置中的设备密钥
+    #[arg(long = "to", value_name = "NAME")]
+    recipients: Vec<String>,
+
+    /// 要运行的命令及参数
+    #[arg(
+        required = true,
+        trailing_var_arg = true,
+        allow_hyphen_values = true,
+        value_name = "COMMAND"
+    )]
+    command: Vec<String>,
+}
+
+/// 服务器和客户端选项
+#[derive(Args)]
+struct ConnectionArgs {
+    /// Bark 服务器地址 [默认: https://api.day.app]
+    #[arg(long, env = "BARK_SERVER")]
+    server: Option<String>,
+
+    /// 设备密钥，重复使用或以逗号分隔时推送到多个设备
+    #[arg(
+        short,
+        long = "key",
+        value_name = "KEY",
+        env = "BARK_KEY",
+        value_delimiter = ',',
+        hide_env_values = true
+    )]
+    keys: Vec<String>,
+
+    /// 配置文件路径 [默认: ~/.config/bark/config.toml]
+    #[arg(long, env = "BARK_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// 使用配置文件中的 profile
+    #[arg(short, long, env = "BARK_PROFILE")]
+    profile: Option<String>,
+
+    /// HTTP Basic 认证，格式为 USER:PASSWORD
+    #[arg(
+        long,
+        env = "BARK_AUTH",
+        value_name = "USER:PASSWORD",
+        hide_env_values = true
+    )]
+    auth: Option<String>,
+
+    /// 请求编码方式
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+
+    /// 请求超时时间（秒）
+    #[arg(long, value_name = "SECONDS")]
+    timeout: Option<f64>,
+
+    /// 最大尝试次数（包括第一次发送）
+    #[arg(long, value_name = "N")]
+    retries: Option<u32>,
+
+    /// 代理服务器地址
+    #[arg(long)]
+    proxy: Option<String>,
+
+    /// 加密模式，需要同时设置 --encryption-key
+    #[arg(long, value_enum, requires = "encryption_key")]
+    encryption: Option<Encryption>,
+
+    /// 加密密钥
+    #[arg(long, env = "BARK_ENCRYPTION_KEY", hide_env_values = true)]
+    encryption_key: Option<String>,
+
+    /// 固定的加密 IV，省略时每条消息随机生成；GCM 模式不接受固定 IV
+    #[arg(long, requires = "encryption")]
+    encryption_iv: Option<String>,
+}
+
+/// 推送参数，与 `BarkMessageBuilder` 的方法一一对应
+#[derive(Args)]
+struct MessageArgs {
+    /// 推送内容，省略时从标准输入读取
+    body: Option<String>,
+
+    /// 推送标题
+    #[arg(short, long)]
+    title: Option<String>,
+
+    /// 推送副标题
+    #[arg(long)]
+    subtitle: Option<String>,
+
+    /// 把推送内容作为 Markdown 发送
+    #[arg(short, long)]
+    markdown: bool,
+
+    /// 推送级别：critical、active、time-sensitive 或 passive
+    #[arg(short, long)]
+    level: Option<Level>,
+
+    /// 重要警告的音量（0-10）
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=10))]
+    volume: Option<u8>,
+
+    /// 角标数字
+    #[arg(long)]
+    badge: Option<u32>,
+
+    /// 重复播放铃声
+    #[arg(long)]
+    call: bool,
+
+    /// 自动复制推送内容
+    #[arg(long)]
+    auto_copy: bool,
+
+    /// 复制的内容
+    #[arg(long)]
+    copy: Option<String>,
+
+    /// 铃声名称
+    #[arg(long)]
+    sound: Option<String>,
+
+    /// 图标 URL
+    #[arg(long)]
+    icon: Option<String>,
+
+    /// 图片 URL
+    #[arg(long)]
+    image: Option<String>,
+
+    /// 消息分组
+    #[arg(short, long)]
+    group: Option<String>,
+
+    /// 已加密的推送内容
+    #[arg(long)]
+    ciphertext: Option<String>,
+
+    /// 已加密内容使用的 IV
+    #[arg(long, requires = "ciphertext")]
+    iv: Option<String>,
+
+    /// 保存推送
+    #[arg(long, conflicts_with = "no_archive")]
+    archive: bool,
+
+    /// 不保存推送
+    #[arg(long)]
+    no_archive: bool,
+
+    /// 点击推送时跳转的 URL
+    #[arg(short, long)]
+    url: Option<String>,
+
+    /// 点击推送时的行为，设置为 none 时不做任何操作
+    #[arg(long)]
+    action: Option<String>,
+
+    /// 推送 ID，相同 ID 的推送会更新已有的通知
+    #[arg(long)]
+    id: Option<String>,
+
+    /// 删除 --id 指定的通知
+    #[arg(long, requires = "id")]
+    delete: bool,
+
+    /// 额外参数，值为合法的 JSON 时按 JSON 解析，可以重复使用
+    #[arg(long = "extra", value_name = "KEY=VALUE", value_parser = parse_extra)]
+    extras: Vec<(String, Value)>,
+
+    /// 设备注册表中的设备或设备组，可以重复使用，指定时不使用 --key 和配置中的设备密钥
+    #[arg(long = "to", value_name = "NAME")]
+    recipients: Vec<String>,
+}
+
+/// 请求编码方式
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Json,
+    Form,
+    Path,
+}
+
+/// 加密模式
+#[derive(Clone, Copy, ValueEnum)]
+enum Encryption {
+    Cbc,
+    Ecb,
+    Gcm,
+}
+
+/// 命令执行失败的原因
+enum Failure {
+    /// 命令行用法错误
+    Usage(clap::Error),
+
+    /// 推送失败
+    Bark(BarkError),
+
+    /// 错误已经输出，以指定的退出码退出
+    Exit(u8),
+}
+
+impl From<BarkError> for Failure {
+    fn from(error: BarkError) -> Self {
+        Failure::Bark(error)
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(error) => return exit_with_usage(error),
+    };
+
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Failure::Usage(error)) => exit_with_usage(error),
+        Err(Failure::Bark(error)) => {
+            eprintln!("bark: {}", error);
+            ExitCode::from(error.exit_code())
+        }
+        Err(Failure::Exit(code)) => ExitCode::from(code),
+    }
+}
+
+/// 打印 clap 的错误或帮助信息，并返回对应的退出码
+fn exit_with_usage(error: clap::Error) -> ExitCode {
+    let _ = error.print();
+    usage_exit_code(&error)
+}
+
+/// clap 错误对应的退出码，`--help` 和 `--version` 不是错误
+fn usage_exit_code(error: &clap::Error) -> ExitCode {
+    if error.use_stderr() {
+        ExitCode::from(EXIT_USAGE)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Failure> {
+    if let Some(Commands::Exec(args)) = cli.command {
+        return exec(args);
+    }
+
+    let (client, profile) = connect(&cli.connection)?;
+    let message = build_message(profile.message()?, cli.message)?;
+
+    // 只有一个设备时它是客户端的默认设备密钥，多个设备时 `device_keys` 已经写入消息
+    if message.device_keys.is_none() && message.recipients.is_empty() {
+        client.send(&message)?;
+        return Ok(());
+    }
+
+    let report = client.send_batch(&message)?;
+    let mut first_error = None;
+    for delivery in report.failed() {
+        if let Err(error) = &delivery.result {
+            eprintln!("bark: {}: {}", delivery.device_key, error);
+            first_error.get_or_insert_with(|| error.exit_code());
+        }
+    }
+    match first_error {
+        Some(code) => Err(Failure::Exit(code)),
+        None => Ok(()),
+    }
+}
+
+/// `bark exec`：运行命令并推送结果，以子进程的退出码退出
+fn exec(args: ExecArgs) -> Result<(), Failure> {
+    let (client, profile) = connect(&args.connection)?;
+
+    let mut message = profile.message()?;
+    if let Some(group) = &args.group {
+        message = message.group(group);
+    }
+    if let Some(sound) = &args.sound {
+        message = message.sound(sound);
+    }
+    if let Some(url) = &args.url {
+        message = message.url(url);
+    }
+    for name in &args.recipients {
+        message = message.to(name);
+    }
+
+    let mut options = ExecOptions::default()
+        .only_on_failure(args.only_on_failure)
+        .tail_lines(args.tail_lines)
+        .message(with_recipients(message.build()));
+    if let Some(secs) = args.min_duration {
+        let duration = Duration::try_from_secs_f64(secs)
+            .map_err(|_| BarkError::InvalidConfig(format!("invalid duration: {}", secs)))?;
+        options = options.min_duration(duration);
+    }
+    if let Some(template) = &args.success_title {
+        options = options.success_title(template);
+    }
+    if let Some(template) = &args.success_body {
+        options = options.success_body(template);
+    }
+    if let Some(template) = &args.failure_title {
+        options = options.failure_title(template);
+    }
+    if let Some(template) = &args.failure_body {
+        options = options.failure_body(template);
+    }
+    if let Some(level) = args.success_level {
+        options = options.success_level(level);
+    }
+    if let Some(level) = args.failure_level {
+        options = options.failure_level(level);
+    }
+
+    let mut command = std::process::Command::new(&args.command[0]);
+    command.args(&args.command[1..]);
+    let report = match run_and_notify(&client, &mut command, &options) {
+        Ok(report) => report,
+        Err(error) => {
+            eprintln!("bark: cannot run {}: {}", args.command[0], error);
+            // 与 shell 的约定一致：命令不存在时为 127，无法执行时为 126
+            let not_found = matches!(
+                &error,
+                BarkError::IoError(e) if e.kind() == std::io::ErrorKind::NotFound
+            );
+            return Err(Failure::Exit(if not_found { 127 } else { 126 }));
+        }
+    };
+
+    let mut code = report.exit_code();
+    if let Some(Err(error)) = &report.notification {
+        eprintln!("bark: {}", error);
+        if code == 0 {
+            code = error.exit_code();
+        }
+    }
+    match code {
+        0 => Ok(()),
+        code => Err(Failure::Exit(code)),
+    }
+}
+
+/// 根据命令行参数、环境变量和配置文件创建客户端，命令行参数的优先级最高
+fn connect(args: &ConnectionArgs) -> Result<(SyncBarkClient, Profile), BarkError> {
+    let config = match &args.config {
+        Some(path) => BarkConfig::from_file(path)?.with_env(),
+        None => BarkConfig::load()?,
+    };
+    connect_with(args, config)
+}
+
+/// 在已经读取的配置之上应用命令行参数并创建客户端
+fn connect_with(
+    args: &ConnectionArgs,
+    config: BarkConfig,
+) -> Result<(SyncBarkClient, Profile), BarkError> {
+    let mut overrides = Profile {
+        server: args.server.clone(),
+        device_keys: args.keys.clone(),
+        timeout: args.timeout,
+        retries: args.retries,
+        proxy: args.proxy.clone(),
+        ..Profile::default()
+    };
+    if let Some(auth) = &args.auth {
+        let (username, password) = auth.split_once(':').ok_or_else(|| {
+            BarkError::InvalidConfig("--auth must be in the form USER:PASSWORD".to_string())
+        })?;
+        overrides.username = Some(username.to_string());
+        overrides.password = Some(password.to_string());
+    }
+    let registry = config.registry().clone();
+    let profile = config
+        .with_overrides(&overrides)
+        .profile(args.profile.as_deref())?;
+
+    let mut builder = profile.sync_builder()?.registry(registry);
+    if let Some(format) = args.format {
+        builder = builder.wire_format(match format {
+            Format::Json => WireFormat::Json,
+            Format::Form => WireFormat::Form,
+            Format::Path => WireFormat::Path,
+        });
+    }
+    if let (Some(mode), Some(key)) = (args.encryption, &args.encryption_key) {
+        let mode = match mode {
+            Encryption::Cbc => EncryptionMode::Cbc,
+            Encryption::Ecb => EncryptionMode::Ecb,
+            Encryption::Gcm => EncryptionMode::Gcm,
+        };
+        let mut encryptor = BarkEncryptor::new(mode, key)?;
+        if let Some(iv) = &args.encryption_iv {
+            encryptor = encryptor.with_iv(iv)?;
+        }
+        builder = builder.encryptor(encryptor);
+    }
+
+    Ok((builder.build()?, profile))
+}
+
+/// 在配置的默认推送参数之上应用命令行参数
+fn build_message(
+    mut builder: BarkMessageBuilder,
+    args: MessageArgs,
+) -> Result<BarkMessage, Failure> {
+    let text = match args.body {
+        Some(body) => Some(body),
+        None if args.ciphertext.is_some() => None,
+        None => Some(read_stdin()?),
+    };
+    if let Some(text) = &text {
+        builder = if args.markdown {
+            builder.markdown(text)
+        } else {
+            builder.body(text)
+        };
+    }
+
+    if let Some(title) = &args.title {
+        builder = builder.title(title);
+    }
+    if let Some(subtitle) = &args.subtitle {
+        builder = builder.subtitle(subtitle);
+    }
+    if let Some(level) = args.level {
+        builder = builder.level(level);
+    }
+    if let Some(volume) = args.volume {
+        builder = builder.volume(volume);
+    }
+    if let Some(badge) = args.badge {
+        builder = builder.badge(badge);
+    }
+    if args.call {
+        builder = builder.call(true);
+    }
+    if args.auto_copy {
+        builder = builder.auto_copy(true);
+    }
+    if let Some(copy) = &args.copy {
+        builder = builder.copy(copy);
+    }
+    if let Some(sound) = &args.sound {
+        builder = builder.sound(sound);
+    }
+    if let Some(icon) = &args.icon {
+        builder = builder.icon(icon);
+    }
+    if let Some(image) = &args.image {
+        builder = builder.image(image);
+    }
+    if let Some(group) = &args.group {
+        builder = builder.group(group);
+    }
+    if let Some(ciphertext) = &args.ciphertext {
+        builder = builder.ciphertext(ciphertext);
+    }
+    if let Some(iv) = &args.iv {
+        builder = builder.iv(iv);
+    }
+    if args.archive || args.no_archive {
+        builder = builder.is_archive(args.archive);
+    }
+    if let Some(url) = &args.url {
+        builder = builder.url(url);
+    }
+    if let Some(action) = &args.action {
+        builder = builder.action(action);
+    }
+    if let Some(id) = &args.id {
+        builder = builder.id(id);
+    }
+    if args.delete {
+        builder = builder.delete(true);
+    }
+    for (key, value) in args.extras {
+        builder = builder.extra(&key, value);
+    }
+    for name in &args.recipients {
+        builder = builder.to(name);
+    }
+
+    builder
+        .try_build()
+        .map(with_recipients)
+        .map_err(|error| Failure::Bark(error.into()))
+}
+
+/// 通过 `--to` 指定了收件人时，不再发送给配置中的设备
+fn with_recipients(mut message: BarkMessage) -> BarkMessage {
+    if !message.recipients.is_empty() {
+        message.device_keys = None;
+    }
+    message
+}
+
+/// 从标准输入读取推送内容，去掉末尾的换行
+fn read_stdin() -> Result<String, Failure> {
+    let mut stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        return Err(Failure::Usage(Cli::command().error(
+            ErrorKind::MissingRequiredArgument,
+            "no message body: pass it as an argument or pipe it on stdin",
+        )));
+    }
+
+    let mut body = String::new();
+    stdin
+        .read_to_string(&mut body)
+        .map_err(|error| Failure::Bark(error.into()))?;
+    let trimmed = body.trim_end_matches(['\r', '\n']).len();
+    body.truncate(trimmed);
+    Ok(body)
+}
+
+/// 解析 `KEY=VALUE` 形式的额外参数
+fn parse_extra(value: &str) -> Result<(String, Value), String> {
+    let (key, raw) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got `{}`", value))?;
+    let parsed = serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()));
+    Ok((key.to_string(), parsed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::FromArgMatches;
+    use serde_json::json;
+
+    fn parse(args: &[&str]) -> Cli {
+        Cli::try_parse_from(std::iter::once("bark").chain(args.iter().copied())).unwrap()
+    }
+
+    /// 解析命令行参数，不读取 `BARK_*` 环境变量
+    fn parse_without_env(args: &[&str]) -> Cli {
+        let matches = Cli::command()
+            .mut_args(|arg| arg.env(None))
+            .try_get_matches_from(std::iter::once("bark").chain(args.iter().copied()))
+            .unwrap();
+        Cli::from_arg_matches(&matches).unwrap()
+    }
+
+    fn parse_error(args: &[&str]) -> clap::Error {
+        match Cli::try_parse_from(std::iter::once("bark").chain(args.iter().copied())) {
+            Ok(_) => panic!("expected {:?} to be rejected", args),
+            Err(error) => error,
+        }
+    }
+
+    fn build(args: &[&str]) -> BarkMessage {
+        match build_message(BarkMessage::builder(), parse(args).message) {
+            Ok(message) => message,
+            Err(_) => panic!("cannot build message from {:?}", args),
+        }
+    }
+
+    /// 写入临时配置文件，返回文件路径
+    fn write_config(name: &str, content: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bark-cli-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.json");
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_build_message() {
+        let message = build(&[
+            "-t",
+            "备份完成",
+            "--subtitle",
+            "db",
+            "-l",
+            "critical",
+            "--volume",
+            "5",
+            "--badge",
+            "3",
+            "-g",
+            "ops",
+            "--sound",
+            "bell",
+            "--call",
+            "--id",
+            "backup",
+            "共 42 GB",
+        ]);
+        assert_eq!(message.title.as_deref(), Some("备份完成"));
+        assert_eq!(message.subtitle.as_deref(), Some("db"));
+        assert_eq!(message.body, "共 42 GB");
+        assert_eq!(message.level, Some(Level::Critical));
+        assert_eq!(message.volume, Some(5));
+        assert_eq!(message.badge, Some(3));
+        assert_eq!(message.group.as_deref(), Some("ops"));
+        assert_eq!(message.sound.as_deref(), Some("bell"));
+        assert_eq!(message.call, Some(true));
+        assert_eq!(message.id.as_deref(), Some("backup"));
+        assert_eq!(message.is_archive, None);
+
+        let message = build(&["-m", "**粗体**"]);
+        assert_eq!(message.markdown.as_deref(), Some("**粗体**"));
+
+        // 只有密文时不读取标准输入
+        let message = build(&["--ciphertext", "abc", "--iv", "0123456789abcdef"]);
+        assert_eq!(message.ciphertext.as_deref(), Some("abc"));
+    }
+
+    #[test]
+    fn test_archive_flags() {
+        assert_eq!(build(&["--archive", "body"]).is_archive, Some(true));
+        assert_eq!(build(&["--no-archive", "body"]).is_archive, Some(false));
+
+        let error = parse_error(&["--archive", "--no-archive", "body"]);
+        assert_eq!(error.kind(), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn test_extra_values() {
+        let message = build(&[
+            "--extra",
+            "count=3",
+            "--extra",
+            "name=web-1",
+            "--extra",
+            r#"tags=["a","b"]"#,
+            "--extra",
+            "empty=",
+            "body",
+        ]);
+        assert_eq!(message.extra["count"], json!(3));
+        assert_eq!(message.extra["name"], json!("web-1"));
+        assert_eq!(message.extra["tags"], json!(["a", "b"]));
+        assert_eq!(message.extra["empty"], json!(""));
+
+        let error = parse_error(&["--extra", "no-equals-sign", "body"]);
+        assert_eq!(error.kind(), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn test_key_splitting() {
+        let cli = parse(&["-k", "key1,key2", "--key", "key3", "body"]);
+        assert_eq!(cli.connection.keys, ["key1", "key2", "key3"]);
+    }
+
+    #[test]
+    fn test_recipients_replace_device_keys() {
+        let builder = || BarkMessage::builder().device_keys(vec!["key1".into(), "key2".into()]);
+        let cli = parse(&["--to", "oncall", "--to", "alice", "body"]);
+        let message = match build_message(builder(), cli.message) {
+            Ok(message) => message,
+            Err(_) => panic!("cannot build message"),
+        };
+        assert_eq!(message.recipients, ["oncall", "alice"]);
+        assert_eq!(message.device_keys, None);
+
+        // 没有 --to 时保留配置中的设备密钥
+        let message = match build_message(builder(), parse(&["body"]).message) {
+            Ok(message) => message,
+            Err(_) => panic!("cannot build message"),
+        };
+        assert_eq!(
+            message.device_keys,
+            Some(vec!["key1".to_string(), "key2".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_connect() {
+        let path = write_config(
+            "connect",
+            r#"{
+                "server": "https://bark.example.com",
+                "device_key": "config_key",
+                "group": "ops",
+                "profiles": { "dev": { "server": "http://localhost:8080" } },
+                "devices": { "alice": "alice_key" }
+            }"#,
+        );
+        let config = path.to_str().unwrap();
+        // 测试进程的 BARK_* 环境变量不应影响结果
+        let connect = |cli: &Cli| connect_with(&cli.connection, BarkConfig::from_file(&path)?);
+
+        let cli =
+            parse_without_env(&["--config", config, "--server", "http://localhost:1", "body"]);
+        let (_, profile) = connect(&cli).unwrap();
+        assert_eq!(profile.server(), "http://localhost:1");
+        assert_eq!(profile.device_keys().unwrap(), ["config_key"]);
+        let message = build_message(profile.message().unwrap(), cli.message)
+            .ok()
+            .unwrap();
+        assert_eq!(message.group.as_deref(), Some("ops"));
+
+        // 命令行的设备密钥和 profile 覆盖配置文件
+        let cli = parse_without_env(&["--config", config, "-p", "dev", "-k", "k1,k2", "body"]);
+        let (_, profile) = connect(&cli).unwrap();
+        assert_eq!(profile.server(), "http://localhost:8080");
+        assert_eq!(profile.device_keys().unwrap(), ["k1", "k2"]);
+
+        let cli = parse_without_env(&["--config", config, "--auth", "no-colon", "body"]);
+        assert!(matches!(connect(&cli), Err(BarkError::InvalidConfig(_))));
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_exec_args() {
+        let cli = parse(&[
+            "exec",
+            "--only-on-failure",
+            "--to",
+            "oncall",
+            "--",
+            "pg_dump",
+            "-f",
+            "backup.sql",
+        ]);
+        let Some(Commands::Exec(args)) = cli.command else {
+            panic!("expected exec subcommand");
+        };
+        assert!(args.only_on_failure);
+        assert_eq!(args.recipients, ["oncall"]);
+        assert_eq!(args.command, ["pg_dump", "-f", "backup.sql"]);
+        assert_eq!(args.tail_lines, 10);
+
+        assert_eq!(
+            parse_error(&["exec"]).kind(),
+            ErrorKind::MissingRequiredArgument
+        );
+    }
+
+    #[test]
+    fn test_usage_exit_code() {
+        let error = parse_error(&["--volume", "11", "body"]);
+        assert_eq!(error.kind(), ErrorKind::ValueValidation);
+        assert_eq!(usage_exit_code(&error), ExitCode::from(EXIT_USAGE));
+
+        let error = parse_error(&["--delete", "body"]);
+        assert_eq!(error.kind(), ErrorKind::MissingRequiredArgument);
+        assert_eq!(usage_exit_code(&error), ExitCode::from(64));
+
+        assert_eq!(
+            usage_exit_code(&parse_error(&["--help"])),
+            ExitCode::SUCCESS
+        );
+        assert_eq!(
+            usage_exit_code(&parse_error(&["--version"])),
+            ExitCode::SUCCESS
+        );
+    }
+}
@@ -0,0 +1,589 @@
+//! 配置文件模块
+//!
+//! [`BarkConfig`] 从 TOML 或 JSON 配置文件以及 `BARK_*` 环境变量中读取服务器地址、
+//! 设备密钥和默认的推送参数，并直接创建客户端，避免在代码中写死密钥。
+//!
+//! 配置文件顶层的字段是所有 profile 的默认值，`[profiles.<name>]` 中的字段覆盖默认值，
+//! 环境变量的优先级最高：
+//!
+//! ```toml
+//! server = "https://api.day.app"
+//! group = "ops"
+//! default_profile = "prod"
+//!
+//! [profiles.prod]
+//! server = "https://bark.example.com"
+//! device_keys_file = "keys/oncall.txt"   # 每行一个设备密钥，相对于配置文件所在目录
+//! level = "timeSensitive"
+//!
+//! [profiles.dev]
+//! device_keys_env = "DEV_BARK_KEYS"      # 从环境变量读取，以逗号分隔
+//! sound = "bell"
+//!
+//! # 设备注册表，参见 DeviceRegistry
+//! [devices]
+//! alice-iphone = "alice_key"
+//! bob-ipad = { key = "bob_key", server = "https://bark.example.com" }
+//!
+//! [groups]
+//! oncall = ["alice-iphone", "bob-ipad"]
+//! ```
+//!
+//! | 环境变量 | 作用 |
+//! |---|---|
+//! | `BARK_CONFIG` | 配置文件路径，默认为 `~/.config/bark/config.toml` |
+//! | `BARK_PROFILE` | 使用的 profile |
+//! | `BARK_SERVER` | 服务器地址 |
+//! | `BARK_KEY` | 设备密钥，多个以逗号分隔 |
+//! | `BARK_KEYS_FILE` | 设备密钥文件 |
+//! | `BARK_GROUP`、`BARK_ICON`、`BARK_SOUND`、`BARK_LEVEL` | 默认的推送参数 |
+//!
+//! 读取 TOML 格式需要启用 `toml` feature，JSON 格式始终可用。
+//!
+//! # 示例
+//!
+//! ```rust,no_run
+//! use bark_rs::BarkConfig;
+//!
+//! # fn example() -> bark_rs::Result<()> {
+//! let config = BarkConfig::load()?;
+//! let client = config.sync_client(Some("prod"))?;
+//!
+//! let message = config.profile(Some("prod"))?.message()?.body("部署完成").build();
+//! client.send(&message)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    BarkError, BarkMessageBuilder, Device, DeviceRegistry, Level, Result, RetryPolicy,
+    SyncBarkClientBuilder,
+};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[cfg(feature = "async")]
+use crate::AsyncBarkClientBuilder;
+
+/// 未指定服务器时使用的官方服务器
+pub const DEFAULT_SERVER: &str = "https://api.day.app";
+
+/// 没有指定 profile 时使用的名称
+const DEFAULT_PROFILE: &str = "default";
+
+/// 一组客户端设置和默认的推送参数
+///
+/// 通过 [`BarkConfig::profile`] 获取，其中的字段已经合并了配置文件的默认值和环境变量。
+#[derive(Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    /// 服务器地址，默认为 [`DEFAULT_SERVER`]
+    pub server: Option<String>,
+
+    /// 设备密钥
+    pub device_key: Option<String>,
+
+    /// 多个设备密钥
+    pub device_keys: Vec<String>,
+
+    /// 设备密钥文件，每行一个密钥，忽略空行和 `#` 开头的注释
+    pub device_keys_file: Option<PathBuf>,
+
+    /// 保存设备密钥的环境变量名称，多个密钥以逗号或空白分隔
+    pub device_keys_env: Option<String>,
+
+    /// 默认的消息分组
+    pub group: Option<String>,
+
+    /// 默认的图标 URL
+    pub icon: Option<String>,
+
+    /// 默认的铃声
+    pub sound: Option<String>,
+
+    /// 默认的推送级别
+    pub level: Option<Level>,
+
+    /// HTTP Basic 认证的用户名
+    pub username: Option<String>,
+
+    /// HTTP Basic 认证的密码
+    pub password: Option<String>,
+
+    /// 请求超时时间（秒）
+    pub timeout: Option<f64>,
+
+    /// 最大尝试次数（包括第一次发送）
+    pub retries: Option<u32>,
+
+    /// 代理服务器地址
+    pub proxy: Option<String>,
+}
+
+impl std::fmt::Debug for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Profile")
+            .field("server", &self.server)
+            .field("device_key", &self.device_key)
+            .field("device_keys", &self.device_keys)
+            .field("device_keys_file", &self.device_keys_file)
+            .field("device_keys_env", &self.device_keys_env)
+            .field("group", &self.group)
+            .field("icon", &self.icon)
+            .field("sound", &self.sound)
+            .field("level", &self.level)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .field("timeout", &self.timeout)
+            .field("retries", &self.retries)
+            .field("proxy", &self.proxy)
+            .finish()
+    }
+}
+
+impl Profile {
+    /// 用 `other` 中设置了的字段覆盖当前的字段
+    ///
+    /// `other` 设置了任何一种设备密钥来源时，当前的所有设备密钥来源都被替换。
+    pub fn merge(mut self, other: &Profile) -> Profile {
+        if other.has_device_keys() {
+            self.device_key = other.device_key.clone();
+            self.device_keys = other.device_keys.clone();
+            self.device_keys_file = other.device_keys_file.clone();
+            self.device_keys_env = other.device_keys_env.clone();
+        }
+
+        macro_rules! merge {
+            ($($field:ident),*) => {
+                $(
+                    if other.$field.is_some() {
+                        self.$field = other.$field.clone();
+                    }
+                )*
+            };
+        }
+        merge!(
+            server, group, icon, sound, level, username, password, timeout, retries, proxy
+        );
+        self
+    }
+
+    fn has_device_keys(&self) -> bool {
+        self.device_key.is_some()
+            || !self.device_keys.is_empty()
+            || self.device_keys_file.is_some()
+            || self.device_keys_env.is_some()
+    }
+
+    /// 服务器地址
+    pub fn server(&self) -> &str {
+        self.server.as_deref().unwrap_or(DEFAULT_SERVER)
+    }
+
+    /// 从所有来源读取设备密钥
+    ///
+    /// 依次取自 `device_key`、`device_keys`、`device_keys_file` 和 `device_keys_env`，
+    /// 去掉重复的密钥。
+    ///
+    /// # 错误
+    ///
+    /// * [`BarkError::InvalidConfig`] - 无法读取设备密钥文件，或环境变量不存在
+    pub fn device_keys(&self) -> Result<Vec<String>> {
+        let mut keys: Vec<String> = self
+            .device_key
+            .iter()
+            .chain(&self.device_keys)
+            .cloned()
+            .collect();
+
+        if let Some(path) = &self.device_keys_file {
+            let content = std::fs::read_to_string(path).map_err(|error| {
+                BarkError::InvalidConfig(format!(
+                    "cannot read device keys from {}: {}",
+                    path.display(),
+                    error
+                ))
+            })?;
+            keys.extend(
+                content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(str::to_string),
+            );
+        }
+        if let Some(name) = &self.device_keys_env {
+            let value = std::env::var(name).map_err(|_| {
+                BarkError::InvalidConfig(format!("environment variable {} is not set", name))
+            })?;
+            keys.extend(split_keys(&value));
+        }
+
+        let mut unique = Vec::with_capacity(keys.len());
+        for key in keys {
+            if !unique.contains(&key) {
+                unique.push(key);
+            }
+        }
+        Ok(unique)
+    }
+
+    /// 创建带有默认推送参数的消息构建器
+    ///
+    /// 设置了默认的分组、图标、铃声和级别。有多个设备密钥时同时设置 `device_keys`；
+    /// 只有一个设备密钥时它是客户端的默认设备密钥，不写入消息。
+    pub fn message(&self) -> Result<BarkMessageBuilder> {
+        let mut builder = BarkMessageBuilder::new();
+        if let Some(group) = &self.group {
+            builder = builder.group(group);
+        }
+        if let Some(icon) = &self.icon {
+            builder = builder.icon(icon);
+        }
+        if let Some(sound) = &self.sound {
+            builder = builder.sound(sound);
+        }
+        if let Some(level) = &self.level {
+            builder = builder.level(level.clone());
+        }
+        let keys = self.device_keys()?;
+        if keys.len() > 1 {
+            builder = builder.device_keys(keys);
+        }
+        Ok(builder)
+    }
+
+    /// 创建同步客户端构建器
+    ///
+    /// 设置了服务器地址、认证、超时、重试和代理。只有一个设备密钥时设置为默认设备密钥，
+    /// [`message`](Self::message) 中的默认推送参数则作为客户端的默认消息模板，
+    /// 因此客户端发送的每条消息都会使用配置中的分组、图标、铃声、级别和设备。
+    pub fn sync_builder(&self) -> Result<SyncBarkClientBuilder> {
+        let mut builder =
+            crate::SyncBarkClient::builder(self.server()).defaults(self.message()?.build());
+        if let [key] = self.device_keys()?.as_slice() {
+            builder = builder.device_key(key);
+        }
+        if let (Some(username), Some(password)) = (&self.username, &self.password) {
+            builder = builder.basic_auth(username, password);
+        }
+        if let Some(timeout) = self.timeout()? {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(retries) = self.retries {
+            builder = builder.retry_policy(RetryPolicy::new(retries));
+        }
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy);
+        }
+        Ok(builder)
+    }
+
+    /// 创建异步客户端构建器
+    ///
+    /// 与 [`sync_builder`](Self::sync_builder) 的设置相同。
+    #[cfg(feature = "async")]
+    pub fn async_builder(&self) -> Result<AsyncBarkClientBuilder> {
+        let mut builder =
+            crate::AsyncBarkClient::builder(self.server()).defaults(self.message()?.build());
+        if let [key] = self.device_keys()?.as_slice() {
+            builder = builder.device_key(key);
+        }
+        if let (Some(username), Some(password)) = (&self.username, &self.password) {
+            builder = builder.basic_auth(username, password);
+        }
+        if let Some(timeout) = self.timeout()? {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(retries) = self.retries {
+            builder = builder.retry_policy(RetryPolicy::new(retries));
+        }
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy);
+        }
+        Ok(builder)
+    }
+
+    fn timeout(&self) -> Result<Option<Duration>> {
+        self.timeout
+            .map(|secs| {
+                Duration::try_from_secs_f64(secs)
+                    .map_err(|_| BarkError::InvalidConfig(format!("invalid timeout: {}", secs)))
+            })
+            .transpose()
+    }
+
+    /// 相对路径以 `base` 为基准
+    fn resolve_paths(&mut self, base: &Path) {
+        if let Some(path) = &mut self.device_keys_file {
+            if path.is_relative() {
+                *path = base.join(&*path);
+            }
+        }
+    }
+}
+
+/// 配置文件的内容
+///
+/// 顶层的 profile 字段逐一列出而不使用 `#[serde(flatten)]`，这样拼错的顶层字段也会被拒绝。
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    default_profile: Option<String>,
+
+    #[serde(default)]
+    profiles: BTreeMap<String, Profile>,
+
+    #[serde(default)]
+    devices: BTreeMap<String, Device>,
+
+    #[serde(default)]
+    groups: BTreeMap<String, Vec<String>>,
+
+    server: Option<String>,
+    device_key: Option<String>,
+    #[serde(default)]
+    device_keys: Vec<String>,
+    device_keys_file: Option<PathBuf>,
+    device_keys_env: Option<String>,
+    group: Option<String>,
+    icon: Option<String>,
+    sound: Option<String>,
+    level: Option<Level>,
+    username: Option<String>,
+    password: Option<String>,
+    timeout: Option<f64>,
+    retries: Option<u32>,
+    proxy: Option<String>,
+}
+
+/// 客户端配置
+///
+/// 包含所有 profile 的默认值、命名的 profile、设备注册表和环境变量的设置。
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BarkConfig {
+    defaults: Profile,
+    profiles: BTreeMap<String, Profile>,
+    default_profile: Option<String>,
+    overrides: Profile,
+    registry: DeviceRegistry,
+}
+
+impl BarkConfig {
+    /// 从默认位置读取配置文件，并应用环境变量
+    ///
+    /// 配置文件取自 `BARK_CONFIG`，未设置时为 `$XDG_CONFIG_HOME/bark/config.toml` 或
+    /// `~/.config/bark/config.toml`。默认位置的文件不存在时只使用环境变量。
+    pub fn load() -> Result<Self> {
+        let config = match std::env::var_os("BARK_CONFIG") {
+            Some(path) => Self::from_file(path)?,
+            None => match default_path().filter(|path| path.exists()) {
+                Some(path) => Self::from_file(path)?,
+                None => Self::default(),
+            },
+        };
+        Ok(config.with_env())
+    }
+
+    /// 只使用环境变量的配置
+    pub fn from_env() -> Self {
+        Self::default().with_env()
+    }
+
+    /// 读取配置文件
+    ///
+    /// 扩展名为 `.json` 时按 JSON 解析，否则按 TOML 解析（需要启用 `toml` feature）。
+    /// 设备密钥文件的相对路径以配置文件所在的目录为基准。不会应用环境变量。
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|error| {
+            BarkError::InvalidConfig(format!("cannot read {}: {}", path.display(), error))
+        })?;
+
+        let is_json = path.extension().is_some_and(|ext| ext == "json");
+        let config = if is_json {
+            Self::from_json_str(&content)
+        } else {
+            Self::parse_toml(&content)
+        };
+        let mut config = config.map_err(|error| match error {
+            BarkError::InvalidConfig(message) => {
+                BarkError::InvalidConfig(format!("{}: {}", path.display(), message))
+            }
+            error => error,
+        })?;
+
+        if let Some(base) = path.parent() {
+            config.defaults.resolve_paths(base);
+            for profile in config.profiles.values_mut() {
+                profile.resolve_paths(base);
+            }
+        }
+        Ok(config)
+    }
+
+    /// 解析 JSON 格式的配置
+    pub fn from_json_str(content: &str) -> Result<Self> {
+        let file: ConfigFile = serde_json::from_str(content)
+            .map_err(|error| BarkError::InvalidConfig(error.to_string()))?;
+        file.try_into()
+    }
+
+    /// 解析 TOML 格式的配置
+    #[cfg(feature = "toml")]
+    pub fn from_toml_str(content: &str) -> Result<Self> {
+        let file: ConfigFile =
+            toml::from_str(content).map_err(|error| BarkError::InvalidConfig(error.to_string()))?;
+        file.try_into()
+    }
+
+    #[cfg(feature = "toml")]
+    fn parse_toml(content: &str) -> Result<Self> {
+        Self::from_toml_str(content)
+    }
+
+    #[cfg(not(feature = "toml"))]
+    fn parse_toml(_content: &str) -> Result<Self> {
+        Err(BarkError::InvalidConfig(
+            "reading TOML requires the `toml` feature".to_string(),
+        ))
+    }
+
+    /// 应用 `BARK_*` 环境变量，环境变量的优先级高于配置文件
+    ///
+    /// 无法识别的 `BARK_LEVEL` 会被忽略。
+    pub fn with_env(mut self) -> Self {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+
+        if let Some(profile) = var("BARK_PROFILE") {
+            self.default_profile = Some(profile);
+        }
+        let overrides = &mut self.overrides;
+        overrides.server = var("BARK_SERVER").or(overrides.server.take());
+        if let Some(keys) = var("BARK_KEY") {
+            overrides.device_keys = split_keys(&keys).collect();
+        }
+        if let Some(path) = var("BARK_KEYS_FILE") {
+            overrides.device_keys_file = Some(PathBuf::from(path));
+        }
+        overrides.group = var("BARK_GROUP").or(overrides.group.take());
+        overrides.icon = var("BARK_ICON").or(overrides.icon.take());
+        overrides.sound = var("BARK_SOUND").or(overrides.sound.take());
+        if let Some(level) = var("BARK_LEVEL").and_then(|level| level.parse().ok()) {
+            overrides.level = Some(level);
+        }
+        self
+    }
+
+    /// 用 `overrides` 中设置了的字段覆盖所有 profile
+    ///
+    /// 命令行工具等可以用这个方法让自己的参数优先于配置文件和环境变量。
+    pub fn with_overrides(mut self, overrides: &Profile) -> Self {
+        self.overrides = self.overrides.merge(overrides);
+        self
+    }
+
+    /// 配置文件中的 profile 名称
+    pub fn profile_names(&self) -> impl Iterator<Item = &str> {
+        self.profiles.keys().map(String::as_str)
+    }
+
+    /// 获取合并后的 profile
+    ///
+    /// `name` 为 `None` 时依次使用 `BARK_PROFILE`、配置文件的 `default_profile` 和
+    /// 名为 `default` 的 profile，都不存在时只使用默认值。
+    ///
+    /// # 错误
+    ///
+    /// * [`BarkError::InvalidConfig`] - 指定的 profile 不存在
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile> {
+        let name = name.or(self.default_profile.as_deref());
+        let empty = Profile::default();
+        let profile = match name {
+            Some(name) => self.profiles.get(name).ok_or_else(|| {
+                let known: Vec<&str> = self.profile_names().collect();
+                BarkError::InvalidConfig(format!(
+                    "unknown profile `{}` (available: {})",
+                    name,
+                    known.join(", ")
+                ))
+            })?,
+            None => self.profiles.get(DEFAULT_PROFILE).unwrap_or(&empty),
+        };
+        Ok(self.defaults.clone().merge(profile).merge(&self.overrides))
+    }
+
+    /// 配置文件中 `[devices]` 和 `[groups]` 定义的设备注册表
+    pub fn registry(&self) -> &DeviceRegistry {
+        &self.registry
+    }
+
+    /// 使用指定的 profile 创建同步客户端
+    ///
+    /// 客户端同时使用配置中的设备注册表。
+    pub fn sync_client(&self, profile: Option<&str>) -> Result<crate::SyncBarkClient> {
+        self.profile(profile)?
+            .sync_builder()?
+            .registry(self.registry.clone())
+            .build()
+    }
+
+    /// 使用指定的 profile 创建异步客户端
+    ///
+    /// 客户端同时使用配置中的设备注册表。
+    #[cfg(feature = "async")]
+    pub fn async_client(&self, profile: Option<&str>) -> Result<crate::AsyncBarkClient> {
+        self.profile(profile)?
+            .async_builder()?
+            .registry(self.registry.clone())
+            .build()
+    }
+}
+
+impl TryFrom<ConfigFile> for BarkConfig {
+    type Error = BarkError;
+
+    fn try_from(file: ConfigFile) -> Result<Self> {
+        let defaults = Profile {
+            server: file.server,
+            device_key: file.device_key,
+            device_keys: file.device_keys,
+            device_keys_file: file.device_keys_file,
+            device_keys_env: file.device_keys_env,
+            group: file.group,
+            icon: file.icon,
+            sound: file.sound,
+            level: file.level,
+            username: file.username,
+            password: file.password,
+            timeout: file.timeout,
+            retries: file.retries,
+            proxy: file.proxy,
+        };
+        Ok(Self {
+            defaults,
+            profiles: file.profiles,
+            default_profile: file.default_profile,
+            overrides: Profile::default(),
+            registry: DeviceRegistry::from_parts(file.devices, file.groups)?,
+        })
+    }
+}
+
+/// 默认的配置文件位置：`$XDG_CONFIG_HOME/bark/config.toml` 或 `~/.config/bark/config.toml`
+fn default_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_home.join("bark").join("config.toml"))
+}
+
+/// 拆分以逗号或空白分隔的设备密钥
+fn split_keys(value: &str) -> impl Iterator<Item = String> + '_ {
+    value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|key| !key.is_empty())
+        .map(str::to_string)
+}
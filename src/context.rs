@@ -0,0 +1,186 @@
+//! 消息上下文模块
+//!
+//! [`MessageContext`] 描述发送推送的服务、运行环境和主机名，客户端通过
+//! [`with_context`](crate::SyncBarkClient::with_context) 把它写入每条消息的副标题或分组，
+//! 便于在手机上区分同一个 Bark 账号下多个服务、多台机器发来的推送。
+//!
+//! 上下文与 [`with_defaults`](crate::SyncBarkClient::with_defaults) 设置的默认消息模板一样，
+//! 只填充没有设置的字段，并且可以通过 [`MessageField`](crate::MessageField) 按消息排除。
+//!
+//! # 示例
+//!
+//! ```rust
+//! use bark_rs::{ContextPlacement, MessageContext, SyncBarkClient};
+//!
+//! let context = MessageContext::new()
+//!     .service("订单服务")
+//!     .environment("prod")
+//!     .host("web-1")
+//!     .placement(ContextPlacement::Subtitle);
+//! assert_eq!(context.render().as_deref(), Some("订单服务 · prod · web-1"));
+//!
+//! let client = SyncBarkClient::with_device_key("https://api.day.app", "your_key")
+//!     .with_context(context);
+//! ```
+
+use crate::BarkMessage;
+use std::borrow::Cow;
+
+/// 上下文各部分之间的分隔符
+const SEPARATOR: &str = " · ";
+
+/// 上下文写入消息的位置
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ContextPlacement {
+    /// 写入副标题，这是默认值
+    #[default]
+    Subtitle,
+
+    /// 写入分组，同一上下文的推送会被折叠在一起
+    Group,
+}
+
+/// 自动附加到消息上的上下文信息
+///
+/// 非空的部分按服务名、环境、主机名的顺序以 ` · ` 连接。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MessageContext {
+    /// 服务名称
+    pub service: Option<String>,
+
+    /// 运行环境，如 `prod`、`staging`
+    pub environment: Option<String>,
+
+    /// 主机名
+    pub host: Option<String>,
+
+    /// 写入消息的位置
+    pub placement: ContextPlacement,
+}
+
+impl MessageContext {
+    /// 创建空的上下文
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 从运行环境中检测上下文
+    ///
+    /// 服务名取自 `BARK_SERVICE` 环境变量，没有设置时使用当前程序的名称；
+    /// 环境取自 `BARK_ENV` 环境变量；主机名参见 [`hostname`](Self::hostname)。
+    pub fn detect() -> Self {
+        let service = std::env::var("BARK_SERVICE")
+            .ok()
+            .filter(|service| !service.is_empty())
+            .unwrap_or_else(program_name);
+        Self {
+            service: Some(service),
+            environment: std::env::var("BARK_ENV").ok().filter(|env| !env.is_empty()),
+            host: hostname(),
+            placement: ContextPlacement::default(),
+        }
+    }
+
+    /// 设置服务名称
+    pub fn service(mut self, service: &str) -> Self {
+        self.service = Some(service.to_string());
+        self
+    }
+
+    /// 设置运行环境
+    pub fn environment(mut self, environment: &str) -> Self {
+        self.environment = Some(environment.to_string());
+        self
+    }
+
+    /// 设置主机名
+    pub fn host(mut self, host: &str) -> Self {
+        self.host = Some(host.to_string());
+        self
+    }
+
+    /// 使用当前主机名
+    ///
+    /// 依次尝试 `HOSTNAME`、`COMPUTERNAME` 环境变量和 `/proc/sys/kernel/hostname`、`/etc/hostname`，
+    /// 都无法获取时不设置主机名。
+    pub fn hostname(mut self) -> Self {
+        self.host = hostname();
+        self
+    }
+
+    /// 设置写入消息的位置
+    pub fn placement(mut self, placement: ContextPlacement) -> Self {
+        self.placement = placement;
+        self
+    }
+
+    /// 上下文的文本形式，所有部分都为空时返回 `None`
+    pub fn render(&self) -> Option<String> {
+        let parts: Vec<&str> = [&self.service, &self.environment, &self.host]
+            .into_iter()
+            .filter_map(|part| part.as_deref())
+            .filter(|part| !part.is_empty())
+            .collect();
+        (!parts.is_empty()).then(|| parts.join(SEPARATOR))
+    }
+
+    /// 只包含上下文的消息，用作默认消息模板
+    fn to_message(&self) -> BarkMessage {
+        let text = self.render();
+        match self.placement {
+            ContextPlacement::Subtitle => BarkMessage {
+                subtitle: text,
+                ..BarkMessage::default()
+            },
+            ContextPlacement::Group => BarkMessage {
+                group: text,
+                ..BarkMessage::default()
+            },
+        }
+    }
+}
+
+/// 依次用默认消息模板和上下文补全消息
+pub(crate) fn apply_defaults<'a>(
+    message: &'a BarkMessage,
+    defaults: Option<&BarkMessage>,
+    context: Option<&MessageContext>,
+) -> Cow<'a, BarkMessage> {
+    let mut message = Cow::Borrowed(message);
+    if let Some(defaults) = defaults {
+        message = Cow::Owned(message.merge_defaults(defaults));
+    }
+    if let Some(context) = context {
+        message = Cow::Owned(message.merge_defaults(&context.to_message()));
+    }
+    message
+}
+
+/// 当前程序的名称
+pub(crate) fn program_name() -> String {
+    std::env::args()
+        .next()
+        .as_deref()
+        .map(std::path::Path::new)
+        .and_then(|path| path.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "process".to_string())
+}
+
+/// 当前主机名
+///
+/// 依次尝试 `HOSTNAME`、`COMPUTERNAME` 环境变量和 `/proc/sys/kernel/hostname`、`/etc/hostname`。
+pub(crate) fn hostname() -> Option<String> {
+    let from_env = ["HOSTNAME", "COMPUTERNAME"]
+        .iter()
+        .find_map(|name| std::env::var(name).ok());
+    let from_file = || {
+        ["/proc/sys/kernel/hostname", "/etc/hostname"]
+            .iter()
+            .find_map(|path| std::fs::read_to_string(path).ok())
+    };
+    from_env
+        .or_else(from_file)
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
@@ -0,0 +1,377 @@
+//! Bark 推送加密模块
+//!
+//! 这个模块实现了 Bark 的端到端加密推送协议：将消息中的敏感字段序列化为 JSON，
+//! 使用 AES（CBC / ECB / GCM）加密后以 Base64 编码放入 `ciphertext` 参数，
+//! 并通过 `iv` 参数告知 App 本次使用的初始向量。
+//!
+//! 密钥长度决定 AES 的位数：16 字节为 AES-128，24 字节为 AES-192，32 字节为 AES-256。
+//! 密钥和 IV 均按照 Bark App 的约定以 UTF-8 字符串形式提供。
+//!
+//! # 示例
+//!
+//! ```rust
+//! use bark_rs::{BarkEncryptor, BarkMessage, EncryptionMode};
+//!
+//! let encryptor = BarkEncryptor::new(EncryptionMode::Cbc, "1234567890123456")?;
+//!
+//! let message = BarkMessage::builder()
+//!     .title("机密标题")
+//!     .body("机密内容")
+//!     .build();
+//!
+//! let encrypted = encryptor.encrypt(&message)?;
+//! assert!(encrypted.ciphertext.is_some());
+//! assert!(encrypted.iv.is_some());
+//! assert!(encrypted.title.is_none());
+//! # Ok::<(), bark_rs::BarkError>(())
+//! ```
+
+use crate::{BarkError, BarkMessage, Result};
+use aes::cipher::block_padding::Pkcs7;
+use aes::cipher::{BlockDecryptMut, BlockEncryptMut, KeyInit, KeyIvInit};
+use aes::{Aes128, Aes192, Aes256};
+use aes_gcm::aead::Aead;
+use aes_gcm::aead::consts::U12;
+use aes_gcm::{Aes128Gcm, Aes256Gcm, AesGcm, Nonce};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde_json::{Map, Value};
+
+/// CBC 模式使用的 IV 长度（字节）
+const CBC_IV_LEN: usize = 16;
+
+/// GCM 模式使用的 IV 长度（字节）
+const GCM_IV_LEN: usize = 12;
+
+/// AES-192-GCM（`aes-gcm` 只预定义了 128 和 256 位的别名）
+type Aes192Gcm = AesGcm<Aes192, U12>;
+
+/// 随机 IV 使用的字符集，保证生成的 IV 是合法的 UTF-8 字符串
+const IV_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// 加密模式
+///
+/// 需要与 Bark App 中「推送加密」设置的模式保持一致。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncryptionMode {
+    /// CBC 模式，PKCS7 填充，IV 长度为 16 字节
+    Cbc,
+
+    /// ECB 模式，PKCS7 填充，不使用 IV
+    Ecb,
+
+    /// GCM 模式，无填充，IV 长度为 12 字节，认证标签附加在密文末尾
+    Gcm,
+}
+
+impl EncryptionMode {
+    /// 当前模式要求的 IV 长度，ECB 模式不需要 IV
+    fn iv_len(&self) -> Option<usize> {
+        match self {
+            EncryptionMode::Cbc => Some(CBC_IV_LEN),
+            EncryptionMode::Ecb => None,
+            EncryptionMode::Gcm => Some(GCM_IV_LEN),
+        }
+    }
+}
+
+/// Bark 推送加密器
+///
+/// 持有密钥、加密模式和可选的固定 IV。未设置固定 IV 时，每条消息都会生成一个新的随机 IV。
+/// 加密器可以通过 [`SyncBarkClient::with_encryptor`](crate::SyncBarkClient::with_encryptor)
+/// 挂载到客户端上，使所有发送的消息自动加密。
+///
+/// # 示例
+///
+/// ```rust
+/// use bark_rs::{BarkEncryptor, EncryptionMode};
+///
+/// // AES-256-GCM，每条消息随机 IV
+/// let encryptor = BarkEncryptor::new(EncryptionMode::Gcm, "12345678901234567890123456789012")?;
+///
+/// // AES-128-CBC，使用固定 IV（需与 App 中的设置一致）
+/// let encryptor = BarkEncryptor::new(EncryptionMode::Cbc, "1234567890123456")?
+///     .with_iv("1111111111111111")?;
+/// # Ok::<(), bark_rs::BarkError>(())
+/// ```
+#[derive(Clone)]
+pub struct BarkEncryptor {
+    /// 加密模式
+    mode: EncryptionMode,
+
+    /// 密钥（16、24 或 32 字节）
+    key: Vec<u8>,
+
+    /// 可选的固定 IV
+    iv: Option<String>,
+}
+
+impl std::fmt::Debug for BarkEncryptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BarkEncryptor")
+            .field("mode", &self.mode)
+            .field("key", &format_args!("<{} bits>", self.key.len() * 8))
+            .field("iv", &self.iv)
+            .finish()
+    }
+}
+
+impl BarkEncryptor {
+    /// 创建新的加密器
+    ///
+    /// # 参数
+    ///
+    /// * `mode` - 加密模式
+    /// * `key` - 密钥字符串，长度必须为 16、24 或 32 字节
+    ///
+    /// # 错误
+    ///
+    /// 密钥长度不合法时返回 [`BarkError::EncryptionError`]
+    pub fn new(mode: EncryptionMode, key: &str) -> Result<Self> {
+        if !matches!(key.len(), 16 | 24 | 32) {
+            return Err(BarkError::EncryptionError(format!(
+                "key must be 16, 24 or 32 bytes, got {}",
+                key.len()
+            )));
+        }
+
+        Ok(Self {
+            mode,
+            key: key.as_bytes().to_vec(),
+            iv: None,
+        })
+    }
+
+    /// 设置固定 IV
+    ///
+    /// CBC 模式要求 16 字节，ECB 模式不接受 IV。GCM 模式也不接受固定 IV：同一个密钥下
+    /// 重复使用 nonce 会同时破坏 GCM 的保密性和完整性校验，因此 GCM 模式总是为每条消息
+    /// 生成随机 IV。
+    ///
+    /// # 错误
+    ///
+    /// IV 长度与模式不匹配或者在 GCM 模式下调用时返回 [`BarkError::EncryptionError`]
+    pub fn with_iv(mut self, iv: &str) -> Result<Self> {
+        if self.mode == EncryptionMode::Gcm {
+            return Err(BarkError::EncryptionError(
+                "GCM mode does not allow a fixed iv, a random iv is generated for every message"
+                    .to_string(),
+            ));
+        }
+        self.check_iv(iv)?;
+        self.iv = Some(iv.to_string());
+        Ok(self)
+    }
+
+    /// 获取加密模式
+    pub fn mode(&self) -> EncryptionMode {
+        self.mode
+    }
+
+    /// 加密消息
+    ///
+    /// 将消息中除设备密钥以外的字段序列化为 JSON 并加密，返回一个只包含
+    /// `ciphertext`、`iv` 以及设备密钥的新消息。已经包含 `ciphertext` 的消息会原样返回。
+    ///
+    /// # 错误
+    ///
+    /// * [`BarkError::SerializationError`] - 序列化失败
+    /// * [`BarkError::EncryptionError`] - 加密失败
+    pub fn encrypt(&self, message: &BarkMessage) -> Result<BarkMessage> {
+        if message.ciphertext.is_some() {
+            return Ok(message.clone());
+        }
+
+        let plaintext = serde_json::to_string(&envelope(message)?)?;
+        let iv = match self.mode.iv_len() {
+            Some(_) if self.iv.is_some() => self.iv.clone(),
+            Some(len) => Some(random_iv(len)?),
+            None => None,
+        };
+        let ciphertext = self.encrypt_bytes(plaintext.as_bytes(), iv.as_deref())?;
+
+        Ok(BarkMessage {
+            device_key: message.device_key.clone(),
+            device_keys: message.device_keys.clone(),
+            ciphertext: Some(BASE64.encode(ciphertext)),
+            iv,
+            ..BarkMessage::default()
+        })
+    }
+
+    /// 解密 `ciphertext`，返回明文 JSON 字符串
+    ///
+    /// 主要用于测试和调试。未传入 `iv` 时使用加密器的固定 IV。
+    ///
+    /// # 错误
+    ///
+    /// 密文格式错误、IV 缺失或校验失败时返回 [`BarkError::EncryptionError`]
+    pub fn decrypt(&self, ciphertext: &str, iv: Option<&str>) -> Result<String> {
+        let data = BASE64
+            .decode(ciphertext)
+            .map_err(|e| BarkError::EncryptionError(format!("invalid base64: {}", e)))?;
+        let iv = iv.or(self.iv.as_deref());
+        let plaintext = self.decrypt_bytes(&data, iv)?;
+
+        String::from_utf8(plaintext)
+            .map_err(|e| BarkError::EncryptionError(format!("invalid utf-8 plaintext: {}", e)))
+    }
+
+    /// 校验 IV 长度
+    fn check_iv(&self, iv: &str) -> Result<()> {
+        match self.mode.iv_len() {
+            Some(len) if iv.len() == len => Ok(()),
+            Some(len) => Err(BarkError::EncryptionError(format!(
+                "iv must be {} bytes in {:?} mode, got {}",
+                len,
+                self.mode,
+                iv.len()
+            ))),
+            None => Err(BarkError::EncryptionError(
+                "ECB mode does not use an iv".to_string(),
+            )),
+        }
+    }
+
+    /// 取出模式要求的 IV 字节
+    fn iv_bytes<'a>(&self, iv: Option<&'a str>) -> Result<&'a [u8]> {
+        let iv = iv.ok_or_else(|| BarkError::EncryptionError("missing iv".to_string()))?;
+        self.check_iv(iv)?;
+        Ok(iv.as_bytes())
+    }
+
+    fn encrypt_bytes(&self, plaintext: &[u8], iv: Option<&str>) -> Result<Vec<u8>> {
+        match self.mode {
+            EncryptionMode::Cbc => {
+                let iv = self.iv_bytes(iv)?;
+                Ok(match self.key.len() {
+                    16 => cbc::Encryptor::<Aes128>::new_from_slices(&self.key, iv)
+                        .map_err(invalid_length)?
+                        .encrypt_padded_vec_mut::<Pkcs7>(plaintext),
+                    24 => cbc::Encryptor::<Aes192>::new_from_slices(&self.key, iv)
+                        .map_err(invalid_length)?
+                        .encrypt_padded_vec_mut::<Pkcs7>(plaintext),
+                    _ => cbc::Encryptor::<Aes256>::new_from_slices(&self.key, iv)
+                        .map_err(invalid_length)?
+                        .encrypt_padded_vec_mut::<Pkcs7>(plaintext),
+                })
+            }
+            EncryptionMode::Ecb => Ok(match self.key.len() {
+                16 => ecb::Encryptor::<Aes128>::new_from_slice(&self.key)
+                    .map_err(invalid_length)?
+                    .encrypt_padded_vec_mut::<Pkcs7>(plaintext),
+                24 => ecb::Encryptor::<Aes192>::new_from_slice(&self.key)
+                    .map_err(invalid_length)?
+                    .encrypt_padded_vec_mut::<Pkcs7>(plaintext),
+                _ => ecb::Encryptor::<Aes256>::new_from_slice(&self.key)
+                    .map_err(invalid_length)?
+                    .encrypt_padded_vec_mut::<Pkcs7>(plaintext),
+            }),
+            EncryptionMode::Gcm => {
+                let nonce = Nonce::from_slice(self.iv_bytes(iv)?);
+                let result = match self.key.len() {
+                    16 => Aes128Gcm::new_from_slice(&self.key)
+                        .map_err(invalid_length)?
+                        .encrypt(nonce, plaintext),
+                    24 => Aes192Gcm::new_from_slice(&self.key)
+                        .map_err(invalid_length)?
+                        .encrypt(nonce, plaintext),
+                    _ => Aes256Gcm::new_from_slice(&self.key)
+                        .map_err(invalid_length)?
+                        .encrypt(nonce, plaintext),
+                };
+                result.map_err(|_| BarkError::EncryptionError("AES-GCM encryption failed".into()))
+            }
+        }
+    }
+
+    fn decrypt_bytes(&self, data: &[u8], iv: Option<&str>) -> Result<Vec<u8>> {
+        let padding_error = |_| BarkError::EncryptionError("invalid padding".to_string());
+
+        match self.mode {
+            EncryptionMode::Cbc => {
+                let iv = self.iv_bytes(iv)?;
+                match self.key.len() {
+                    16 => cbc::Decryptor::<Aes128>::new_from_slices(&self.key, iv)
+                        .map_err(invalid_length)?
+                        .decrypt_padded_vec_mut::<Pkcs7>(data),
+                    24 => cbc::Decryptor::<Aes192>::new_from_slices(&self.key, iv)
+                        .map_err(invalid_length)?
+                        .decrypt_padded_vec_mut::<Pkcs7>(data),
+                    _ => cbc::Decryptor::<Aes256>::new_from_slices(&self.key, iv)
+                        .map_err(invalid_length)?
+                        .decrypt_padded_vec_mut::<Pkcs7>(data),
+                }
+                .map_err(padding_error)
+            }
+            EncryptionMode::Ecb => match self.key.len() {
+                16 => ecb::Decryptor::<Aes128>::new_from_slice(&self.key)
+                    .map_err(invalid_length)?
+                    .decrypt_padded_vec_mut::<Pkcs7>(data),
+                24 => ecb::Decryptor::<Aes192>::new_from_slice(&self.key)
+                    .map_err(invalid_length)?
+                    .decrypt_padded_vec_mut::<Pkcs7>(data),
+                _ => ecb::Decryptor::<Aes256>::new_from_slice(&self.key)
+                    .map_err(invalid_length)?
+                    .decrypt_padded_vec_mut::<Pkcs7>(data),
+            }
+            .map_err(padding_error),
+            EncryptionMode::Gcm => {
+                let nonce = Nonce::from_slice(self.iv_bytes(iv)?);
+                let result = match self.key.len() {
+                    16 => Aes128Gcm::new_from_slice(&self.key)
+                        .map_err(invalid_length)?
+                        .decrypt(nonce, data),
+                    24 => Aes192Gcm::new_from_slice(&self.key)
+                        .map_err(invalid_length)?
+                        .decrypt(nonce, data),
+                    _ => Aes256Gcm::new_from_slice(&self.key)
+                        .map_err(invalid_length)?
+                        .decrypt(nonce, data),
+                };
+                result.map_err(|_| BarkError::EncryptionError("AES-GCM tag mismatch".into()))
+            }
+        }
+    }
+}
+
+fn invalid_length(_: aes::cipher::InvalidLength) -> BarkError {
+    BarkError::EncryptionError("invalid key or iv length".to_string())
+}
+
+/// 生成指定长度的随机字母数字 IV
+///
+/// 系统随机数生成器不可用时返回 [`BarkError::EncryptionError`]。
+fn random_iv(len: usize) -> Result<String> {
+    let mut iv = String::with_capacity(len);
+    let mut buf = [0u8; 32];
+
+    while iv.len() < len {
+        getrandom::fill(&mut buf).map_err(|err| {
+            BarkError::EncryptionError(format!(
+                "system random number generator unavailable: {}",
+                err
+            ))
+        })?;
+        // 丢弃超出字符集整数倍的字节，避免取模带来的偏差
+        let limit = (256 / IV_ALPHABET.len() * IV_ALPHABET.len()) as u8;
+        for byte in buf.iter().filter(|b| **b < limit) {
+            if iv.len() == len {
+                break;
+            }
+            iv.push(IV_ALPHABET[*byte as usize % IV_ALPHABET.len()] as char);
+        }
+    }
+
+    Ok(iv)
+}
+
+/// 构建需要加密的 JSON 信封
+///
+/// 包含除设备密钥以外的所有消息字段，字段名和取值格式与 Bark API 一致。
+fn envelope(message: &BarkMessage) -> Result<Map<String, Value>> {
+    let mut payload = message.to_payload()?;
+    payload.remove("device_keys");
+    payload.remove("iv");
+    Ok(payload)
+}
@@ -0,0 +1,323 @@
+//! 防抖推送模块
+//!
+//! 进度、状态之类的通知经常在短时间内多次更新，而用户只关心最新的一条。[`Debouncer`]
+//! 按消息的 `id`（没有 `id` 时按消息内容）合并更新：同一个键的消息在安静期内没有新的更新后，
+//! 才在后台线程中发送最后一条，之前被替换的消息不会发送。
+//!
+//! 异步版本参见 `AsyncDebouncer`（需要启用 `async` feature）。
+//!
+//! # 示例
+//!
+//! ```rust,no_run
+//! use bark_rs::{BarkMessage, Debouncer, SyncBarkClient};
+//! use std::time::Duration;
+//!
+//! let client = SyncBarkClient::with_device_key("https://api.day.app", "your_key");
+//! let debouncer = Debouncer::builder(client)
+//!     .quiet_period(Duration::from_secs(5))
+//!     .spawn();
+//!
+//! for percent in [10, 50, 90, 100] {
+//!     let message = BarkMessage::builder()
+//!         .id("backup")
+//!         .body(&format!("备份进度 {}%", percent))
+//!         .build();
+//!     debouncer.submit(message).ok();
+//! }
+//!
+//! // 关闭时立即发送等待中的消息，这里只会发送 "备份进度 100%"
+//! let report = debouncer.shutdown();
+//! println!("发送 {} 条，合并 {} 条", report.delivered, report.superseded);
+//! ```
+
+use crate::dedupe::{self, DedupeKey};
+use crate::registry::Route;
+use crate::worker::{EnqueueError, FailureHandler};
+use crate::{BarkError, BarkMessage, SyncBarkClient};
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// 默认安静期
+pub const DEFAULT_QUIET_PERIOD: Duration = Duration::from_secs(2);
+
+/// 关闭防抖任务的结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DebounceReport {
+    /// 发送成功的消息数量
+    pub delivered: usize,
+
+    /// 发送失败的消息数量
+    pub failed: usize,
+
+    /// 被同一个键的新消息替换、没有发送的消息数量
+    pub superseded: usize,
+}
+
+/// 等待发送的消息
+struct Pending {
+    message: BarkMessage,
+    due: Instant,
+}
+
+/// 同步和异步防抖任务共享的状态
+#[derive(Default)]
+pub(crate) struct DebounceState {
+    pending: HashMap<String, Pending>,
+    closed: bool,
+    delivered: usize,
+    failed: usize,
+    superseded: usize,
+}
+
+/// 下一步操作
+#[allow(clippy::large_enum_variant)]
+pub(crate) enum Next {
+    /// 发送一条安静期已过的消息
+    Message(BarkMessage),
+
+    /// 等待到指定时间，或者没有等待中的消息时一直等待新消息
+    Wait(Option<Instant>),
+
+    /// 已关闭且没有等待中的消息
+    Exit,
+}
+
+impl DebounceState {
+    /// 提交消息，替换同一个键等待中的消息
+    pub(crate) fn submit(
+        &mut self,
+        key: String,
+        message: BarkMessage,
+        due: Instant,
+    ) -> Result<(), EnqueueError> {
+        if self.closed {
+            return Err(EnqueueError::Closed(Box::new(message)));
+        }
+        if self.pending.insert(key, Pending { message, due }).is_some() {
+            self.superseded += 1;
+        }
+        Ok(())
+    }
+
+    /// 取出最早到期的消息；关闭后所有消息立即到期
+    pub(crate) fn next(&mut self, now: Instant) -> Next {
+        let earliest = self
+            .pending
+            .iter()
+            .min_by_key(|(_, pending)| pending.due)
+            .map(|(key, pending)| (key.clone(), pending.due));
+
+        match earliest {
+            Some((key, due)) if self.closed || due <= now => {
+                Next::Message(self.pending.remove(&key).unwrap().message)
+            }
+            Some((_, due)) => Next::Wait(Some(due)),
+            None if self.closed => Next::Exit,
+            None => Next::Wait(None),
+        }
+    }
+
+    /// 记录一条消息的发送结果
+    pub(crate) fn record(&mut self, success: bool) {
+        if success {
+            self.delivered += 1;
+        } else {
+            self.failed += 1;
+        }
+    }
+
+    pub(crate) fn close(&mut self) {
+        self.closed = true;
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub(crate) fn report(&self) -> DebounceReport {
+        DebounceReport {
+            delivered: self.delivered,
+            failed: self.failed,
+            superseded: self.superseded,
+        }
+    }
+}
+
+/// 防抖合并使用的键：`id` 和目标设备，没有 `id` 时使用消息内容
+///
+/// `targets` 是补全了默认值、解析了收件人后实际发送的消息。
+pub(crate) fn debounce_key(targets: &[Route], default_device_key: Option<&str>) -> String {
+    let messages = targets.iter().map(|route| &route.message);
+    dedupe::message_key(messages, DedupeKey::Id, default_device_key)
+}
+
+/// 同步防抖任务的共享状态
+struct Shared {
+    state: Mutex<DebounceState>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, DebounceState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// 在后台线程中合并并发送快速更新的消息
+///
+/// 通过 [`Debouncer::builder`] 创建。不调用 [`shutdown`](Self::shutdown) 直接丢弃时，
+/// 等待中的消息仍会在后台线程中立即发送。
+pub struct Debouncer {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+    quiet_period: Duration,
+    client: SyncBarkClient,
+}
+
+/// [`Debouncer`] 构建器
+pub struct DebouncerBuilder {
+    client: SyncBarkClient,
+    quiet_period: Duration,
+    on_failure: Option<FailureHandler>,
+}
+
+impl Debouncer {
+    /// 创建防抖任务构建器
+    ///
+    /// 默认安静期为 [`DEFAULT_QUIET_PERIOD`]。
+    pub fn builder(client: SyncBarkClient) -> DebouncerBuilder {
+        DebouncerBuilder {
+            client,
+            quiet_period: DEFAULT_QUIET_PERIOD,
+            on_failure: None,
+        }
+    }
+
+    /// 提交一条消息
+    ///
+    /// 消息在安静期结束后发送；安静期内提交了同一个键的新消息时，这条消息被替换，
+    /// 安静期重新开始计算。防抖任务关闭后返回 [`EnqueueError::Closed`]。
+    pub fn submit(&self, message: BarkMessage) -> Result<(), EnqueueError> {
+        // 收件人无法解析时按原始消息合并，发送时的错误交给 on_failure 处理
+        let targets = self
+            .client
+            .targets(&message)
+            .unwrap_or_else(|_| vec![message.clone().into()]);
+        let key = debounce_key(&targets, self.client.default_device_key.as_deref());
+        let due = Instant::now() + self.quiet_period;
+        self.shared.lock().submit(key, message, due)?;
+        self.shared.changed.notify_one();
+        Ok(())
+    }
+
+    /// 等待发送的消息数量
+    pub fn len(&self) -> usize {
+        self.shared.lock().len()
+    }
+
+    /// 是否没有等待发送的消息
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 关闭防抖任务
+    ///
+    /// 不再接受新消息，立即发送所有等待中的消息，等待发送完成后返回结果。
+    pub fn shutdown(mut self) -> DebounceReport {
+        self.shared.lock().close();
+        self.shared.changed.notify_one();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        self.shared.lock().report()
+    }
+}
+
+impl Drop for Debouncer {
+    fn drop(&mut self) {
+        self.shared.lock().close();
+        self.shared.changed.notify_one();
+    }
+}
+
+impl DebouncerBuilder {
+    /// 设置安静期
+    pub fn quiet_period(mut self, quiet_period: Duration) -> Self {
+        self.quiet_period = quiet_period;
+        self
+    }
+
+    /// 设置发送失败时的回调
+    ///
+    /// 后台发送的错误无法返回给调用方，可以在这里记录日志。回调在后台线程中执行。
+    pub fn on_failure<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&BarkMessage, &BarkError) + Send + Sync + 'static,
+    {
+        self.on_failure = Some(Arc::new(handler));
+        self
+    }
+
+    /// 启动后台线程
+    pub fn spawn(self) -> Debouncer {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(DebounceState::default()),
+            changed: Condvar::new(),
+        });
+        let thread = {
+            let shared = shared.clone();
+            let client = self.client.clone();
+            let on_failure = self.on_failure;
+            std::thread::Builder::new()
+                .name("bark-debouncer".to_string())
+                .spawn(move || run(&shared, &client, on_failure.as_ref()))
+                .expect("failed to spawn bark debouncer thread")
+        };
+
+        Debouncer {
+            shared,
+            thread: Some(thread),
+            quiet_period: self.quiet_period,
+            client: self.client,
+        }
+    }
+}
+
+/// 后台线程的主循环
+fn run(shared: &Shared, client: &SyncBarkClient, on_failure: Option<&FailureHandler>) {
+    loop {
+        let message = {
+            let mut state = shared.lock();
+            loop {
+                match state.next(Instant::now()) {
+                    Next::Message(message) => break message,
+                    Next::Wait(Some(due)) => {
+                        let timeout = due.saturating_duration_since(Instant::now());
+                        state = shared
+                            .changed
+                            .wait_timeout(state, timeout)
+                            .unwrap_or_else(|poisoned| poisoned.into_inner())
+                            .0;
+                    }
+                    Next::Wait(None) => {
+                        state = shared
+                            .changed
+                            .wait(state)
+                            .unwrap_or_else(|poisoned| poisoned.into_inner());
+                    }
+                    Next::Exit => return,
+                }
+            }
+        };
+
+        let result = client.send(&message);
+        if let (Err(error), Some(on_failure)) = (&result, on_failure) {
+            on_failure(&message, error);
+        }
+        shared.lock().record(result.is_ok());
+    }
+}
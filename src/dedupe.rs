@@ -0,0 +1,477 @@
+//! 消息去重模块
+//!
+//! Bark 会替换 `id` 相同的通知，但客户端仍然会发送每一条重复的消息。[`Deduplicator`]
+//! 在发送前计算消息的去重键，同一个键在时间窗口内只发送一次，重复的消息返回
+//! [`BarkError::Suppressed`](crate::BarkError::Suppressed)。
+//!
+//! 去重键由 [`DedupeKey`] 决定：默认根据标题、副标题、内容、密文、分组和目标设备计算，
+//! 也可以只根据 `id` 和目标设备计算。
+//!
+//! 已发送的键保存在 [`DedupeStore`] 中：[`MemoryDedupeStore`] 只在当前进程内有效，
+//! [`FileDedupeStore`] 保存在文件中，可以在多个定时任务进程之间共享。
+//!
+//! 对于同一个 `id` 的快速更新只发送最后一条的防抖处理，参见 [`Debouncer`](crate::Debouncer)。
+//!
+//! # 示例
+//!
+//! ```rust,no_run
+//! use bark_rs::{BarkMessage, DedupedClient, Deduplicator, FileDedupeStore, SyncBarkClient};
+//! use std::time::Duration;
+//!
+//! # fn example() -> bark_rs::Result<()> {
+//! let deduplicator = Deduplicator::new(Duration::from_secs(3600))
+//!     .with_store(FileDedupeStore::new("/var/tmp/bark-dedupe.json"));
+//!
+//! let client = SyncBarkClient::with_device_key("https://api.day.app", "your_key");
+//! let client = DedupedClient::new(client, deduplicator);
+//!
+//! // 每 5 分钟运行一次的定时任务，一小时内只会推送一次
+//! client.send(&BarkMessage::builder().body("证书即将过期").build())?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::registry::{self, Route};
+use crate::{BarkError, BarkMessage, BarkResponse, Result, Suppression, SyncBarkClient};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "async")]
+use crate::AsyncBarkClient;
+
+/// 等待文件锁的最长时间
+const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 超过这个时间的锁文件被认为是崩溃的进程遗留的
+const STALE_LOCK: Duration = Duration::from_secs(30);
+
+/// 去重键的计算方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DedupeKey {
+    /// 根据标题、副标题、内容、密文、分组和目标设备计算
+    #[default]
+    Content,
+
+    /// 根据 `id` 和目标设备计算，没有设置 `id` 的消息按内容计算
+    Id,
+}
+
+/// 已发送消息的去重键存储
+///
+/// 实现需要保证 [`insert`](Self::insert) 的检查和写入是原子的，
+/// 这样多个线程或进程同时发送同一条消息时只有一个会成功。
+pub trait DedupeStore: Send + Sync {
+    /// 记录去重键，有效期为 `ttl`
+    ///
+    /// 键不存在或已经过期时返回 `true`；键仍然有效时返回 `false`，不修改有效期。
+    fn insert(&self, key: &str, ttl: Duration) -> Result<bool>;
+
+    /// 删除去重键，键不存在时什么也不做
+    ///
+    /// 发送失败时调用，撤销 [`insert`](Self::insert) 的记录，使之后的重试不会被当作重复消息。
+    fn remove(&self, key: &str) -> Result<()>;
+}
+
+/// 保存在内存中的去重键存储
+///
+/// 只在当前进程内有效。
+#[derive(Debug, Default)]
+pub struct MemoryDedupeStore {
+    keys: Mutex<HashMap<String, Instant>>,
+}
+
+impl MemoryDedupeStore {
+    /// 创建空的存储
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl DedupeStore for MemoryDedupeStore {
+    fn insert(&self, key: &str, ttl: Duration) -> Result<bool> {
+        let now = Instant::now();
+        let mut keys = self
+            .keys
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        keys.retain(|_, expires_at| *expires_at > now);
+
+        if keys.contains_key(key) {
+            return Ok(false);
+        }
+        keys.insert(key.to_string(), now + ttl);
+        Ok(true)
+    }
+
+    fn remove(&self, key: &str) -> Result<()> {
+        self.keys
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .remove(key);
+        Ok(())
+    }
+}
+
+/// 保存在文件中的去重键存储
+///
+/// 文件内容是去重键到过期时间（Unix 毫秒）的 JSON 对象。读写期间通过同目录下的
+/// `.lock` 文件互斥，因此可以被同一台机器上的多个进程共享；文件损坏时按空存储处理。
+#[derive(Debug, Clone)]
+pub struct FileDedupeStore {
+    path: PathBuf,
+}
+
+impl FileDedupeStore {
+    /// 使用指定的文件，文件不存在时会在第一次写入时创建
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// 存储文件的路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn lock_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".lock");
+        path.into()
+    }
+
+    fn read(&self) -> Result<HashMap<String, u64>> {
+        match fs::read(&self.path) {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes).unwrap_or_default()),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn write(&self, keys: &HashMap<String, u64>) -> Result<()> {
+        let mut temp_path = self.path.clone().into_os_string();
+        temp_path.push(".tmp");
+        let temp_path = PathBuf::from(temp_path);
+
+        let mut temp = File::create(&temp_path)?;
+        temp.write_all(&serde_json::to_vec(keys)?)?;
+        temp.sync_data()?;
+        fs::rename(&temp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+impl DedupeStore for FileDedupeStore {
+    fn insert(&self, key: &str, ttl: Duration) -> Result<bool> {
+        let _lock = FileLock::acquire(self.lock_path())?;
+        let now = now_millis();
+        let mut keys = self.read()?;
+        keys.retain(|_, expires_at| *expires_at > now);
+
+        if keys.contains_key(key) {
+            return Ok(false);
+        }
+        keys.insert(key.to_string(), now.saturating_add(ttl.as_millis() as u64));
+        self.write(&keys)?;
+        Ok(true)
+    }
+
+    fn remove(&self, key: &str) -> Result<()> {
+        let _lock = FileLock::acquire(self.lock_path())?;
+        let mut keys = self.read()?;
+        if keys.remove(key).is_some() {
+            self.write(&keys)?;
+        }
+        Ok(())
+    }
+}
+
+/// 通过独占创建锁文件实现的进程间互斥锁，释放时删除锁文件
+struct FileLock {
+    path: PathBuf,
+}
+
+impl FileLock {
+    fn acquire(path: PathBuf) -> Result<Self> {
+        let started = Instant::now();
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(Self { path }),
+                Err(error) if error.kind() == ErrorKind::AlreadyExists => {
+                    let stale = fs::metadata(&path)
+                        .and_then(|metadata| metadata.modified())
+                        .ok()
+                        .and_then(|modified| modified.elapsed().ok())
+                        .is_some_and(|age| age > STALE_LOCK);
+                    if stale {
+                        let _ = fs::remove_file(&path);
+                        continue;
+                    }
+                    if started.elapsed() > LOCK_TIMEOUT {
+                        return Err(std::io::Error::new(
+                            ErrorKind::TimedOut,
+                            format!("timed out waiting for {}", path.display()),
+                        )
+                        .into());
+                    }
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                Err(error) => return Err(error.into()),
+            }
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// 消息去重器
+///
+/// 克隆的去重器共享同一个存储。
+#[derive(Clone)]
+pub struct Deduplicator {
+    store: Arc<dyn DedupeStore>,
+    window: Duration,
+    key: DedupeKey,
+}
+
+impl std::fmt::Debug for Deduplicator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Deduplicator")
+            .field("window", &self.window)
+            .field("key", &self.key)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Deduplicator {
+    /// 创建去重器，同一条消息在 `window` 时间内只发送一次
+    ///
+    /// 默认使用 [`MemoryDedupeStore`]，按消息内容去重。
+    pub fn new(window: Duration) -> Self {
+        Self {
+            store: Arc::new(MemoryDedupeStore::new()),
+            window,
+            key: DedupeKey::default(),
+        }
+    }
+
+    /// 替换去重键存储
+    pub fn with_store(mut self, store: impl DedupeStore + 'static) -> Self {
+        self.store = Arc::new(store);
+        self
+    }
+
+    /// 设置去重键的计算方式
+    pub fn with_key(mut self, key: DedupeKey) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// 计算一个请求的去重键
+    ///
+    /// 没有指定设备密钥的消息使用 `default_device_key` 作为目标设备。这是内部使用的键：
+    /// 客户端按补全了默认消息模板和上下文、解析了收件人的消息计算，
+    /// 直接传入原始消息得到的键与存储中的键不一定相同。
+    pub(crate) fn key(&self, message: &BarkMessage, default_device_key: Option<&str>) -> String {
+        message_key([message], self.key, default_device_key)
+    }
+
+    /// 检查每个请求是否在时间窗口内发送过，记录没有发送过的请求并返回它们和各自的去重键
+    ///
+    /// `targets` 是一次发送实际发出的请求，通过设备注册表发送给多组设备时有多条。
+    /// 每个请求单独去重，部分设备发送失败后重试时只会发送给这些设备。
+    /// 所有请求都发送过时返回 [`BarkError::Suppressed`]。
+    pub(crate) fn check<'t>(
+        &self,
+        targets: &'t [Route],
+        default_device_key: Option<&str>,
+    ) -> Result<Vec<(&'t Route, String)>> {
+        let mut fresh = Vec::new();
+        let mut duplicate = None;
+        for route in targets {
+            let key = self.key(&route.message, default_device_key);
+            match self.store.insert(&key, self.window) {
+                Ok(true) => fresh.push((route, key)),
+                Ok(false) => {
+                    duplicate.get_or_insert(key);
+                }
+                Err(error) => {
+                    for (_, key) in &fresh {
+                        let _ = self.store.remove(key);
+                    }
+                    return Err(error);
+                }
+            }
+        }
+
+        match duplicate {
+            Some(key) if fresh.is_empty() => Err(BarkError::Suppressed(Suppression::Duplicate {
+                key,
+                window: self.window,
+            })),
+            _ => Ok(fresh),
+        }
+    }
+
+    /// 发送完成后调用，发送失败时删除 [`check`](Self::check) 记录的去重键
+    ///
+    /// 删除失败时仍然返回发送的错误。
+    pub(crate) fn finish<T>(&self, key: &str, result: Result<T>) -> Result<T> {
+        if result.is_err() {
+            let _ = self.store.remove(key);
+        }
+        result
+    }
+}
+
+/// 带去重的同步客户端
+///
+/// 发送前检查 [`Deduplicator`]，时间窗口内重复的消息不会发送。
+/// 消息在发送前就被记录，避免并发的重复消息同时发送；发送失败时记录会被删除，
+/// 因此失败的消息可以立即重试。去重键按补全了客户端默认消息模板和上下文、
+/// 解析了收件人的消息计算，通过设备注册表发送给多组设备时每组单独记录，
+/// 重试时已经收到推送的设备不会再收到。
+#[derive(Debug, Clone)]
+pub struct DedupedClient {
+    client: SyncBarkClient,
+    deduplicator: Deduplicator,
+}
+
+impl DedupedClient {
+    /// 使用去重器包装客户端
+    pub fn new(client: SyncBarkClient, deduplicator: Deduplicator) -> Self {
+        Self {
+            client,
+            deduplicator,
+        }
+    }
+
+    /// 被包装的客户端
+    pub fn client(&self) -> &SyncBarkClient {
+        &self.client
+    }
+
+    /// 发送消息，重复的消息返回 [`BarkError::Suppressed`]
+    pub fn send(&self, message: &BarkMessage) -> Result<BarkResponse> {
+        let targets = self.client.targets(message)?;
+        let fresh = self
+            .deduplicator
+            .check(&targets, self.client.default_device_key.as_deref())?;
+        registry::combine(fresh.iter().map(|(route, key)| {
+            let result = self.client.send_route(route);
+            (*route, self.deduplicator.finish(key, result))
+        }))
+    }
+}
+
+/// 带去重的异步客户端
+///
+/// [`DedupedClient`] 的异步版本。存储的读写是同步的，[`FileDedupeStore`]
+/// 会短暂阻塞当前线程。
+#[cfg(feature = "async")]
+#[derive(Debug, Clone)]
+pub struct AsyncDedupedClient {
+    client: AsyncBarkClient,
+    deduplicator: Deduplicator,
+}
+
+#[cfg(feature = "async")]
+impl AsyncDedupedClient {
+    /// 使用去重器包装客户端
+    pub fn new(client: AsyncBarkClient, deduplicator: Deduplicator) -> Self {
+        Self {
+            client,
+            deduplicator,
+        }
+    }
+
+    /// 被包装的客户端
+    pub fn client(&self) -> &AsyncBarkClient {
+        &self.client
+    }
+
+    /// 发送消息，重复的消息返回 [`BarkError::Suppressed`]
+    pub async fn send(&self, message: &BarkMessage) -> Result<BarkResponse> {
+        let targets = self.client.targets(message)?;
+        let fresh = self
+            .deduplicator
+            .check(&targets, self.client.default_device_key.as_deref())?;
+        let mut results = Vec::with_capacity(fresh.len());
+        for (route, key) in &fresh {
+            let result = self.client.send_route(route).await;
+            results.push((*route, self.deduplicator.finish(key, result)));
+        }
+        registry::combine(results)
+    }
+}
+
+/// 计算一次发送的去重键，`messages` 是这次发送实际发出的每个请求
+///
+/// 使用 FNV-1a 哈希，结果在不同进程和编译器版本之间保持稳定。
+pub(crate) fn message_key<'m>(
+    messages: impl IntoIterator<Item = &'m BarkMessage>,
+    key: DedupeKey,
+    default_device_key: Option<&str>,
+) -> String {
+    let mut hasher = Fnv::new();
+    for message in messages {
+        match (&message.device_keys, &message.device_key) {
+            (Some(keys), _) => keys.iter().for_each(|key| hasher.field(key)),
+            (None, Some(key)) => hasher.field(key),
+            (None, None) => hasher.field(default_device_key.unwrap_or_default()),
+        }
+
+        match (key, &message.id) {
+            (DedupeKey::Id, Some(id)) => {
+                hasher.field("id");
+                hasher.field(id);
+            }
+            _ => {
+                hasher.field("content");
+                for field in [
+                    &message.title,
+                    &message.subtitle,
+                    &message.markdown,
+                    &message.ciphertext,
+                    &message.group,
+                ] {
+                    hasher.field(field.as_deref().unwrap_or_default());
+                }
+                hasher.field(&message.body);
+            }
+        }
+    }
+    format!("{:016x}", hasher.0)
+}
+
+/// FNV-1a 64 位哈希
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    /// 写入一个字段，字段之间用 `0xff` 分隔（不会出现在 UTF-8 字符串中）
+    fn field(&mut self, value: &str) {
+        for byte in value.bytes().chain([0xff]) {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
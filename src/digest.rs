@@ -0,0 +1,557 @@
+//! 消息摘要模块
+//!
+//! 批处理任务可能产生数百条事件，逐条推送会淹没通知中心。[`Digest`] 收集消息，
+//! 在到达数量阈值、时间窗口结束（由后台线程计时）、显式调用 [`flush`](Digest::flush)
+//! 或被丢弃时，合并为一条摘要推送：
+//!
+//! - 级别取所有消息中最高的级别
+//! - 内容按 `group` 或级别列出消息数量，以及最早的若干条消息，过长时被截断
+//! - `badge` 为合并的消息总数
+//!
+//! 发送给不同设备的消息分别合并。异步版本参见 `AsyncDigest`（需要启用 `async` feature）。
+//!
+//! # 示例
+//!
+//! ```rust,no_run
+//! use bark_rs::{BarkMessage, Digest, DigestGrouping, SyncBarkClient};
+//! use std::time::Duration;
+//!
+//! # fn example() -> bark_rs::Result<()> {
+//! let client = SyncBarkClient::with_device_key("https://api.day.app", "your_key");
+//! let digest = Digest::builder()
+//!     .title("夜间任务")
+//!     .grouping(DigestGrouping::Level)
+//!     .max_messages(500)
+//!     .window(Duration::from_secs(600))
+//!     .build(client);
+//!
+//! for table in ["users", "orders", "payments"] {
+//!     digest.push(BarkMessage::builder().title(table).body("同步完成").build())?;
+//! }
+//!
+//! // 丢弃时也会发送剩余的消息
+//! digest.flush()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::panic::{MAX_BODY_SIZE, truncate};
+use crate::{BarkMessage, Level, MessageField, Result, SyncBarkClient};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "async")]
+use crate::AsyncBarkClient;
+#[cfg(feature = "async")]
+use tokio::sync::Notify;
+
+/// 按时间窗口发送摘要的后台线程名称
+const TIMER_THREAD_NAME: &str = "bark-digest";
+
+/// 摘要默认列出的消息条数
+pub const DEFAULT_DIGEST_ENTRIES: usize = 5;
+
+/// 摘要中消息数量的统计方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DigestGrouping {
+    /// 按 `group` 统计，没有 `group` 的消息统计为 `other`
+    #[default]
+    Group,
+
+    /// 按级别统计，没有设置级别的消息统计为 `active`
+    Level,
+}
+
+/// [`Digest`] 构建器
+#[derive(Debug, Clone)]
+pub struct DigestBuilder {
+    title: Option<String>,
+    grouping: DigestGrouping,
+    max_entries: usize,
+    max_messages: Option<usize>,
+    window: Option<Duration>,
+}
+
+impl Default for DigestBuilder {
+    fn default() -> Self {
+        Self {
+            title: None,
+            grouping: DigestGrouping::default(),
+            max_entries: DEFAULT_DIGEST_ENTRIES,
+            max_messages: None,
+            window: None,
+        }
+    }
+}
+
+impl DigestBuilder {
+    /// 设置摘要的标题，默认为 "N notifications"
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    /// 设置消息数量的统计方式
+    pub fn grouping(mut self, grouping: DigestGrouping) -> Self {
+        self.grouping = grouping;
+        self
+    }
+
+    /// 设置摘要中列出的消息条数，默认为 [`DEFAULT_DIGEST_ENTRIES`]
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// 收集到 `count` 条消息时立即发送摘要
+    pub fn max_messages(mut self, count: usize) -> Self {
+        self.max_messages = Some(count.max(1));
+        self
+    }
+
+    /// 第一条消息收集后经过 `window` 时间发送摘要
+    ///
+    /// 设置后会启动一个后台线程（异步版本为 tokio 任务），没有新消息时也会按时发送摘要。
+    /// 后台发送的错误会被忽略。
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = Some(window);
+        self
+    }
+
+    /// 使用同步客户端创建摘要收集器
+    ///
+    /// 设置了 [`window`](Self::window) 时启动发送摘要的后台线程。
+    pub fn build(self, client: SyncBarkClient) -> Digest {
+        let timed = self.window.is_some();
+        let shared = Arc::new(Shared {
+            client,
+            collector: Mutex::new(Collector::new(self)),
+            changed: Condvar::new(),
+        });
+        let timer = timed.then(|| {
+            let shared = shared.clone();
+            std::thread::Builder::new()
+                .name(TIMER_THREAD_NAME.to_string())
+                .spawn(move || run_timer(&shared))
+                .expect("failed to spawn bark digest thread")
+        });
+        Digest { shared, timer }
+    }
+
+    /// 使用异步客户端创建摘要收集器
+    ///
+    /// 设置了 [`window`](Self::window) 时在当前 tokio 运行时中启动发送摘要的任务，
+    /// 此时必须在 tokio 运行时中调用。
+    #[cfg(feature = "async")]
+    pub fn build_async(self, client: AsyncBarkClient) -> AsyncDigest {
+        let timed = self.window.is_some();
+        let shared = Arc::new(AsyncShared {
+            client,
+            collector: Mutex::new(Collector::new(self)),
+            changed: Notify::new(),
+        });
+        let timer = timed.then(|| tokio::spawn(run_async_timer(shared.clone())));
+        AsyncDigest { shared, timer }
+    }
+}
+
+/// 同步和异步摘要收集器共享的状态
+#[derive(Debug)]
+struct Collector {
+    config: DigestBuilder,
+    messages: Vec<BarkMessage>,
+    started: Option<Instant>,
+    closed: bool,
+}
+
+impl Collector {
+    fn new(config: DigestBuilder) -> Self {
+        Self {
+            config,
+            messages: Vec::new(),
+            started: None,
+            closed: false,
+        }
+    }
+
+    /// 时间窗口结束的时间，没有收集到消息或没有设置时间窗口时为 `None`
+    fn due(&self) -> Option<Instant> {
+        self.started
+            .zip(self.config.window)
+            .map(|(started, window)| started + window)
+    }
+
+    /// 收集一条消息，返回是否需要发送摘要
+    fn push(&mut self, message: BarkMessage) -> bool {
+        let started = *self.started.get_or_insert_with(Instant::now);
+        self.messages.push(message);
+
+        self.config
+            .max_messages
+            .is_some_and(|count| self.messages.len() >= count)
+            || self
+                .config
+                .window
+                .is_some_and(|window| started.elapsed() >= window)
+    }
+
+    /// 取出收集的消息并生成摘要，每组目标设备一条
+    fn take(&mut self) -> Vec<BarkMessage> {
+        self.started = None;
+        let messages = std::mem::take(&mut self.messages);
+
+        let mut targets: Vec<(Target, Vec<BarkMessage>)> = Vec::new();
+        for message in messages {
+            let target = Target::of(&message);
+            match targets.iter_mut().find(|(existing, _)| *existing == target) {
+                Some((_, messages)) => messages.push(message),
+                None => targets.push((target, vec![message])),
+            }
+        }
+
+        targets
+            .into_iter()
+            .map(|(target, messages)| summarize(&self.config, target, &messages))
+            .collect()
+    }
+}
+
+/// 消息的目标设备，以及不从客户端默认消息模板中补全的字段
+///
+/// `recipients` 原样保留在摘要中，发送时由客户端的设备注册表解析。
+#[derive(PartialEq)]
+struct Target {
+    device_key: Option<String>,
+    device_keys: Option<Vec<String>>,
+    recipients: Vec<String>,
+    skip_defaults: Vec<MessageField>,
+}
+
+impl Target {
+    fn of(message: &BarkMessage) -> Self {
+        Self {
+            device_key: message.device_key.clone(),
+            device_keys: message.device_keys.clone(),
+            recipients: message.recipients.clone(),
+            skip_defaults: message.skip_defaults.clone(),
+        }
+    }
+}
+
+/// 把同一组目标设备的消息合并为一条摘要
+fn summarize(config: &DigestBuilder, target: Target, messages: &[BarkMessage]) -> BarkMessage {
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    for message in messages {
+        let bucket = match config.grouping {
+            DigestGrouping::Group => message.group.as_deref().unwrap_or("other"),
+            DigestGrouping::Level => message.level.as_ref().unwrap_or(&Level::Active).as_str(),
+        };
+        match counts.iter_mut().find(|(name, _)| name == bucket) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((bucket.to_string(), 1)),
+        }
+    }
+
+    let mut lines = vec![
+        counts
+            .iter()
+            .map(|(name, count)| format!("{}: {}", name, count))
+            .collect::<Vec<_>>()
+            .join(", "),
+    ];
+    for message in messages.iter().take(config.max_entries) {
+        let text = if message.body.is_empty() {
+            message.markdown.as_deref().unwrap_or_default()
+        } else {
+            &message.body
+        };
+        match &message.title {
+            Some(title) => lines.push(format!("• {}: {}", title, text)),
+            None => lines.push(format!("• {}", text)),
+        }
+    }
+    if messages.len() > config.max_entries {
+        lines.push(format!(
+            "… and {} more",
+            messages.len() - config.max_entries
+        ));
+    }
+
+    let mut body = lines.join("\n");
+    truncate(&mut body, MAX_BODY_SIZE);
+
+    let group = messages[0].group.clone();
+    let same_group = messages.iter().all(|message| message.group == group);
+    let level = messages
+        .iter()
+        .filter_map(|message| message.level.clone())
+        .max_by_key(level_rank);
+
+    BarkMessage {
+        title: Some(
+            config
+                .title
+                .clone()
+                .unwrap_or_else(|| format!("{} notifications", messages.len())),
+        ),
+        body,
+        device_key: target.device_key,
+        device_keys: target.device_keys,
+        recipients: target.recipients,
+        skip_defaults: target.skip_defaults,
+        level,
+        badge: Some(messages.len() as u32),
+        group: group.filter(|_| same_group),
+        ..BarkMessage::default()
+    }
+}
+
+/// 级别的优先顺序，数值越大越重要
+fn level_rank(level: &Level) -> u8 {
+    match level {
+        Level::Passive => 0,
+        Level::Active => 1,
+        Level::TimeSensitive => 2,
+        Level::Critical => 3,
+    }
+}
+
+/// 同步摘要收集器与后台线程共享的状态
+struct Shared {
+    client: SyncBarkClient,
+    collector: Mutex<Collector>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Collector> {
+        self.collector
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn send_all(&self, summaries: &[BarkMessage]) -> Result<usize> {
+        let mut first_error = None;
+        for summary in summaries {
+            if let Err(error) = self.client.send(summary) {
+                first_error.get_or_insert(error);
+            }
+        }
+        match first_error {
+            Some(error) => Err(error),
+            None => Ok(summaries.len()),
+        }
+    }
+}
+
+/// 把多条消息合并为摘要后通过同步客户端发送
+///
+/// 通过 [`Digest::builder`] 创建。被丢弃时会发送剩余的消息，此时的发送错误会被忽略。
+pub struct Digest {
+    shared: Arc<Shared>,
+    timer: Option<JoinHandle<()>>,
+}
+
+impl Digest {
+    /// 创建摘要收集器构建器
+    pub fn builder() -> DigestBuilder {
+        DigestBuilder::default()
+    }
+
+    /// 收集一条消息
+    ///
+    /// 到达数量阈值或时间窗口结束时发送摘要，返回摘要的发送结果。
+    pub fn push(&self, message: BarkMessage) -> Result<()> {
+        let summaries = {
+            let mut collector = self.shared.lock();
+            if !collector.push(message) {
+                self.shared.changed.notify_all();
+                return Ok(());
+            }
+            collector.take()
+        };
+        self.shared.send_all(&summaries).map(|_| ())
+    }
+
+    /// 立即发送收集的消息的摘要
+    ///
+    /// 返回发送的摘要数量。某条摘要发送失败时仍会尝试发送其余的摘要，然后返回第一个错误。
+    pub fn flush(&self) -> Result<usize> {
+        let summaries = self.shared.lock().take();
+        self.shared.send_all(&summaries)
+    }
+
+    /// 已收集、尚未发送的消息数量
+    pub fn len(&self) -> usize {
+        self.shared.lock().messages.len()
+    }
+
+    /// 是否没有尚未发送的消息
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Drop for Digest {
+    fn drop(&mut self) {
+        self.shared.lock().closed = true;
+        self.shared.changed.notify_all();
+        if let Some(timer) = self.timer.take() {
+            let _ = timer.join();
+        }
+        let _ = self.flush();
+    }
+}
+
+/// 后台线程：时间窗口结束时发送摘要
+fn run_timer(shared: &Shared) {
+    let mut collector = shared.lock();
+    while !collector.closed {
+        match collector.due() {
+            Some(due) if Instant::now() >= due => {
+                let summaries = collector.take();
+                drop(collector);
+                let _ = shared.send_all(&summaries);
+                collector = shared.lock();
+            }
+            Some(due) => {
+                let timeout = due.saturating_duration_since(Instant::now());
+                collector = shared
+                    .changed
+                    .wait_timeout(collector, timeout)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .0;
+            }
+            None => {
+                collector = shared
+                    .changed
+                    .wait(collector)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+            }
+        }
+    }
+}
+
+/// 异步摘要收集器与后台任务共享的状态
+#[cfg(feature = "async")]
+struct AsyncShared {
+    client: AsyncBarkClient,
+    collector: Mutex<Collector>,
+    changed: Notify,
+}
+
+#[cfg(feature = "async")]
+impl AsyncShared {
+    fn lock(&self) -> MutexGuard<'_, Collector> {
+        self.collector
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    async fn send_all(&self, summaries: &[BarkMessage]) -> Result<usize> {
+        let mut first_error = None;
+        for summary in summaries {
+            if let Err(error) = self.client.send(summary).await {
+                first_error.get_or_insert(error);
+            }
+        }
+        match first_error {
+            Some(error) => Err(error),
+            None => Ok(summaries.len()),
+        }
+    }
+}
+
+/// 把多条消息合并为摘要后通过异步客户端发送
+///
+/// 通过 [`DigestBuilder::build_async`] 创建。被丢弃时如果还有剩余的消息，
+/// 会在当前 tokio 运行时中启动一个任务发送摘要；不在运行时中时剩余的消息被丢弃。
+#[cfg(feature = "async")]
+pub struct AsyncDigest {
+    shared: Arc<AsyncShared>,
+    timer: Option<tokio::task::JoinHandle<()>>,
+}
+
+#[cfg(feature = "async")]
+impl AsyncDigest {
+    /// 收集一条消息
+    ///
+    /// 到达数量阈值或时间窗口结束时发送摘要，返回摘要的发送结果。
+    pub async fn push(&self, message: BarkMessage) -> Result<()> {
+        let summaries = {
+            let mut collector = self.shared.lock();
+            if !collector.push(message) {
+                self.shared.changed.notify_one();
+                return Ok(());
+            }
+            collector.take()
+        };
+        self.shared.send_all(&summaries).await.map(|_| ())
+    }
+
+    /// 立即发送收集的消息的摘要
+    ///
+    /// 返回发送的摘要数量。某条摘要发送失败时仍会尝试发送其余的摘要，然后返回第一个错误。
+    pub async fn flush(&self) -> Result<usize> {
+        let summaries = self.shared.lock().take();
+        self.shared.send_all(&summaries).await
+    }
+
+    /// 已收集、尚未发送的消息数量
+    pub fn len(&self) -> usize {
+        self.shared.lock().messages.len()
+    }
+
+    /// 是否没有尚未发送的消息
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(feature = "async")]
+impl Drop for AsyncDigest {
+    fn drop(&mut self) {
+        let summaries = {
+            let mut collector = self.shared.lock();
+            collector.closed = true;
+            collector.take()
+        };
+        self.shared.changed.notify_one();
+        if let Some(timer) = self.timer.take() {
+            timer.abort();
+        }
+        if summaries.is_empty() {
+            return;
+        }
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let shared = self.shared.clone();
+            runtime.spawn(async move {
+                let _ = shared.send_all(&summaries).await;
+            });
+        }
+    }
+}
+
+/// 后台任务：时间窗口结束时发送摘要
+#[cfg(feature = "async")]
+async fn run_async_timer(shared: Arc<AsyncShared>) {
+    loop {
+        let (closed, due) = {
+            let collector = shared.lock();
+            (collector.closed, collector.due())
+        };
+        match due {
+            _ if closed => return,
+            Some(due) if Instant::now() >= due => {
+                let summaries = shared.lock().take();
+                let _ = shared.send_all(&summaries).await;
+            }
+            Some(due) => {
+                let due = tokio::time::Instant::from_std(due);
+                let _ = tokio::time::timeout_at(due, shared.changed.notified()).await;
+            }
+            None => shared.changed.notified().await,
+        }
+    }
+}
@@ -0,0 +1,465 @@
+//! 命令执行通知模块
+//!
+//! [`run_and_notify`] 运行一个子进程，记录退出状态、运行时间和最后若干行输出，
+//! 结束后根据成功或失败发送不同级别的推送，用于替代 crontab 中的 `cmd && curl ...`。
+//!
+//! 子进程继承标准输入，标准输出和标准错误在记录的同时原样转发，因此日志不会丢失。
+//!
+//! # 模板
+//!
+//! 标题和内容模板中可以使用以下占位符，其他 `{...}` 保持原样：
+//!
+//! - `{command}` - 命令及参数
+//! - `{status}` - 退出状态，如 `exit code 1` 或 `signal 9`
+//! - `{code}` - 退出码，被信号终止时为空
+//! - `{duration}` - 运行时间，如 `1m 5s`
+//! - `{host}` - 主机名
+//! - `{stdout}`、`{stderr}` - 标准输出、标准错误的最后若干行
+//! - `{output}` - 按到达顺序合并的标准输出和标准错误的最后若干行
+//!
+//! # 示例
+//!
+//! ```rust,no_run
+//! use bark_rs::{ExecOptions, SyncBarkClient, run_and_notify};
+//! use std::process::Command;
+//! use std::time::Duration;
+//!
+//! # fn example() -> bark_rs::Result<()> {
+//! let client = SyncBarkClient::with_device_key("https://api.day.app", "your_key");
+//! let options = ExecOptions::default()
+//!     .only_on_failure(true)
+//!     .failure_title("备份失败: {status}");
+//!
+//! let report = run_and_notify(&client, Command::new("backup.sh").arg("--full"), &options)?;
+//! println!("{} 用时 {:?}", report.status, report.duration);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::context::hostname;
+use crate::panic::{MAX_BODY_SIZE, truncate};
+use crate::{BarkMessage, BarkResponse, Level, Result, SyncBarkClient};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// [`run_and_notify`] 的选项
+#[derive(Debug, Clone)]
+pub struct ExecOptions {
+    /// 命令成功时的标题模板，默认为 `{command} succeeded`
+    pub success_title: String,
+
+    /// 命令成功时的内容模板，默认为 `finished in {duration}` 加上输出
+    pub success_body: String,
+
+    /// 命令失败时的标题模板，默认为 `{command} failed`
+    pub failure_title: String,
+
+    /// 命令失败时的内容模板，默认为退出状态、运行时间和输出
+    pub failure_body: String,
+
+    /// 命令成功时的推送级别，默认为 [`Level::Active`]
+    pub success_level: Level,
+
+    /// 命令失败时的推送级别，默认为 [`Level::TimeSensitive`]
+    pub failure_level: Level,
+
+    /// 只在命令失败时推送
+    pub only_on_failure: bool,
+
+    /// 只在运行时间达到这个值时推送，失败的命令不受影响
+    pub min_duration: Option<Duration>,
+
+    /// 保留的输出行数，默认为 10
+    pub tail_lines: usize,
+
+    /// 推送的其他参数（如分组、铃声、设备密钥）取自这条消息
+    pub message: BarkMessage,
+}
+
+impl Default for ExecOptions {
+    fn default() -> Self {
+        Self {
+            success_title: "{command} succeeded".to_string(),
+            success_body: "finished in {duration}\n{output}".to_string(),
+            failure_title: "{command} failed".to_string(),
+            failure_body: "{status} after {duration}\n{output}".to_string(),
+            success_level: Level::Active,
+            failure_level: Level::TimeSensitive,
+            only_on_failure: false,
+            min_duration: None,
+            tail_lines: 10,
+            message: BarkMessage::default(),
+        }
+    }
+}
+
+impl ExecOptions {
+    /// 设置命令成功时的标题模板
+    pub fn success_title(mut self, template: &str) -> Self {
+        self.success_title = template.to_string();
+        self
+    }
+
+    /// 设置命令成功时的内容模板
+    pub fn success_body(mut self, template: &str) -> Self {
+        self.success_body = template.to_string();
+        self
+    }
+
+    /// 设置命令失败时的标题模板
+    pub fn failure_title(mut self, template: &str) -> Self {
+        self.failure_title = template.to_string();
+        self
+    }
+
+    /// 设置命令失败时的内容模板
+    pub fn failure_body(mut self, template: &str) -> Self {
+        self.failure_body = template.to_string();
+        self
+    }
+
+    /// 设置命令成功时的推送级别
+    pub fn success_level(mut self, level: Level) -> Self {
+        self.success_level = level;
+        self
+    }
+
+    /// 设置命令失败时的推送级别
+    pub fn failure_level(mut self, level: Level) -> Self {
+        self.failure_level = level;
+        self
+    }
+
+    /// 设置是否只在命令失败时推送
+    pub fn only_on_failure(mut self, only_on_failure: bool) -> Self {
+        self.only_on_failure = only_on_failure;
+        self
+    }
+
+    /// 只在运行时间达到 `duration` 时推送成功的命令
+    pub fn min_duration(mut self, duration: Duration) -> Self {
+        self.min_duration = Some(duration);
+        self
+    }
+
+    /// 设置保留的输出行数
+    pub fn tail_lines(mut self, lines: usize) -> Self {
+        self.tail_lines = lines;
+        self
+    }
+
+    /// 设置推送的其他参数
+    ///
+    /// 标题、内容和级别会被模板和选项覆盖。
+    pub fn message(mut self, message: BarkMessage) -> Self {
+        self.message = message;
+        self
+    }
+}
+
+/// [`run_and_notify`] 的执行结果
+#[derive(Debug)]
+pub struct ExecReport {
+    /// 子进程的退出状态
+    pub status: ExitStatus,
+
+    /// 运行时间
+    pub duration: Duration,
+
+    /// 标准输出的最后若干行
+    pub stdout: String,
+
+    /// 标准错误的最后若干行
+    pub stderr: String,
+
+    /// 推送结果，根据选项跳过推送时为 `None`
+    pub notification: Option<Result<BarkResponse>>,
+}
+
+impl ExecReport {
+    /// 子进程是否成功退出
+    pub fn success(&self) -> bool {
+        self.status.success()
+    }
+
+    /// 子进程的退出码，被信号终止时为 128 加信号编号，与 shell 的约定一致
+    pub fn exit_code(&self) -> u8 {
+        if let Some(code) = self.status.code() {
+            // 超出范围的退出码（如 Windows 上的 NTSTATUS）不能截断为 0，否则会被当作成功
+            return if code == 0 {
+                0
+            } else {
+                code.clamp(1, 255) as u8
+            };
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            if let Some(signal) = self.status.signal() {
+                return (128 + signal) as u8;
+            }
+        }
+        1
+    }
+}
+
+/// 运行命令，结束后发送推送
+///
+/// 等待子进程退出后，根据退出状态选择模板和级别，并按照 `only_on_failure`、`min_duration`
+/// 决定是否推送。推送失败不会影响返回值，而是记录在 [`ExecReport::notification`] 中。
+///
+/// # 错误
+///
+/// * [`BarkError::IoError`](crate::BarkError::IoError) - 无法启动子进程，此时不会推送
+pub fn run_and_notify(
+    client: &SyncBarkClient,
+    command: &mut Command,
+    options: &ExecOptions,
+) -> Result<ExecReport> {
+    let description = describe_command(command);
+    let started = Instant::now();
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let combined = Arc::new(Mutex::new(VecDeque::new()));
+    let stdout = child.stdout.take().map(|pipe| {
+        let combined = combined.clone();
+        let lines = options.tail_lines;
+        std::thread::spawn(move || forward(pipe, std::io::stdout(), &combined, lines))
+    });
+    let stderr = child.stderr.take().map(|pipe| {
+        let combined = combined.clone();
+        let lines = options.tail_lines;
+        std::thread::spawn(move || forward(pipe, std::io::stderr(), &combined, lines))
+    });
+
+    let status = child.wait()?;
+    let duration = started.elapsed();
+    let join = |reader: Option<std::thread::JoinHandle<VecDeque<String>>>| {
+        reader
+            .and_then(|reader| reader.join().ok())
+            .map(join_lines)
+            .unwrap_or_default()
+    };
+    let stdout = join(stdout);
+    let stderr = join(stderr);
+    let output = join_lines(std::mem::take(
+        &mut *combined.lock().unwrap_or_else(|e| e.into_inner()),
+    ));
+
+    let success = status.success();
+    let skip = success
+        && (options.only_on_failure || options.min_duration.is_some_and(|min| duration < min));
+
+    let notification = if skip {
+        None
+    } else {
+        let code = status
+            .code()
+            .map(|code| code.to_string())
+            .unwrap_or_default();
+        let mut values = [
+            ("command", description),
+            ("status", describe_status(status)),
+            ("code", code),
+            ("duration", format_duration(duration)),
+            ("host", hostname().unwrap_or_else(|| "unknown".to_string())),
+            ("stdout", stdout.clone()),
+            ("stderr", stderr.clone()),
+            ("output", output),
+        ];
+        let (title, body, level) = if success {
+            (
+                &options.success_title,
+                &options.success_body,
+                &options.success_level,
+            )
+        } else {
+            (
+                &options.failure_title,
+                &options.failure_body,
+                &options.failure_level,
+            )
+        };
+
+        fit_output(body, &mut values);
+        let mut body = render(body, &values).trim_end().to_string();
+        truncate(&mut body, MAX_BODY_SIZE);
+        let message = BarkMessage {
+            title: Some(render(title, &values)),
+            body,
+            level: Some(level.clone()),
+            ..options.message.clone()
+        };
+        Some(client.send(&message))
+    };
+
+    Ok(ExecReport {
+        status,
+        duration,
+        stdout,
+        stderr,
+        notification,
+    })
+}
+
+/// 把子进程的输出原样转发，同时保留最后 `limit` 行
+fn forward(
+    pipe: impl Read,
+    mut sink: impl Write,
+    combined: &Mutex<VecDeque<String>>,
+    limit: usize,
+) -> VecDeque<String> {
+    let mut reader = BufReader::new(pipe);
+    let mut tail = VecDeque::new();
+    let mut line = Vec::new();
+    while let Ok(read) = reader.read_until(b'\n', &mut line) {
+        if read == 0 {
+            break;
+        }
+        let _ = sink.write_all(&line);
+        let _ = sink.flush();
+
+        let text = String::from_utf8_lossy(&line).trim_end().to_string();
+        push_limited(&mut tail, text.clone(), limit);
+        push_limited(
+            &mut combined.lock().unwrap_or_else(|e| e.into_inner()),
+            text,
+            limit,
+        );
+        line.clear();
+    }
+    tail
+}
+
+fn push_limited(lines: &mut VecDeque<String>, line: String, limit: usize) {
+    if limit == 0 {
+        return;
+    }
+    if lines.len() == limit {
+        lines.pop_front();
+    }
+    lines.push_back(line);
+}
+
+fn join_lines(lines: VecDeque<String>) -> String {
+    Vec::from(lines).join("\n")
+}
+
+/// 输出占位符，内容过长时从前面截断
+const OUTPUT_PLACEHOLDERS: [&str; 3] = ["stdout", "stderr", "output"];
+
+/// 从前面截断输出，使渲染后的内容不超过 [`MAX_BODY_SIZE`]
+///
+/// 命令失败的原因通常在输出的最后几行，所以保留末尾，并尽量从完整的一行开始。
+fn fit_output(template: &str, values: &mut [(&str, String)]) {
+    let uses: usize = OUTPUT_PLACEHOLDERS
+        .iter()
+        .map(|name| template.matches(&format!("{{{}}}", name)).count())
+        .sum();
+    if uses == 0 {
+        return;
+    }
+    let without_output: Vec<(&str, String)> = values
+        .iter()
+        .map(|(name, value)| {
+            if OUTPUT_PLACEHOLDERS.contains(name) {
+                (*name, String::new())
+            } else {
+                (*name, value.clone())
+            }
+        })
+        .collect();
+    let budget = MAX_BODY_SIZE.saturating_sub(render(template, &without_output).len()) / uses;
+    for (name, value) in values.iter_mut() {
+        if OUTPUT_PLACEHOLDERS.contains(name) {
+            keep_tail(value, budget);
+        }
+    }
+}
+
+/// 只保留文本最后不超过 `limit` 字节的部分
+fn keep_tail(text: &mut String, limit: usize) {
+    if text.len() <= limit {
+        return;
+    }
+    let mut start = text.len() - limit;
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    if let Some(newline) = text[start..].find('\n') {
+        start += newline + 1;
+    }
+    text.replace_range(..start, "");
+}
+
+/// 替换模板中的占位符，替换后的内容不会再次展开
+fn render(template: &str, values: &[(&str, String)]) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let value = after.find('}').and_then(|end| {
+            values
+                .iter()
+                .find(|(name, _)| *name == &after[..end])
+                .map(|(name, value)| (name.len(), value))
+        });
+        match value {
+            Some((len, value)) => {
+                output.push_str(value);
+                rest = &after[len + 1..];
+            }
+            None => {
+                output.push('{');
+                rest = after;
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+/// 命令及参数，用于推送的标题
+fn describe_command(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|part| part.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 退出状态的描述
+fn describe_status(status: ExitStatus) -> String {
+    if let Some(code) = status.code() {
+        return format!("exit code {}", code);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return format!("signal {}", signal);
+        }
+    }
+    status.to_string()
+}
+
+/// 以易读的形式显示运行时间，如 `1h 2m 3s`、`4.2s`
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        return format!("{:.1}s", duration.as_secs_f64());
+    }
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{}h {}m {}s", hours, minutes, seconds)
+    } else {
+        format!("{}m {}s", minutes, seconds)
+    }
+}
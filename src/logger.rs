@@ -0,0 +1,230 @@
+//! log 集成模块
+//!
+//! [`BarkLogger`] 实现了 `log::Log`，把达到指定级别的日志记录转换为 Bark 推送：
+//!
+//! - 级别：`Error` → [`Level::Critical`]，`Warn` → [`Level::TimeSensitive`]，
+//!   `Info` → [`Level::Active`]，`Debug`/`Trace` → [`Level::Passive`]
+//! - 标题：日志级别和 target（如 `ERROR my_app::payments`）
+//! - 分组：日志的 target
+//! - 内容：日志消息和代码位置
+//!
+//! 推送通过内部的 [`BarkWorker`] 在后台线程中发送，队列已满时直接丢弃新记录，
+//! 因此 `log::error!` 永远不会等待网络。进程退出前调用 `log::logger().flush()`
+//! 可以等待队列中的推送发送完毕（最多 5 秒）。可以通过 [`chain`](BarkLoggerBuilder::chain)
+//! 组合已有的日志实现，所有记录仍会交给它处理。
+//!
+//! # 示例
+//!
+//! ```rust,no_run
+//! use bark_rs::{BarkLogger, SyncBarkClient};
+//! use log::LevelFilter;
+//!
+//! let client = SyncBarkClient::with_device_key("https://api.day.app", "your_key");
+//! BarkLogger::builder(client)
+//!     .level(LevelFilter::Error)
+//!     .module("my_app::payments", LevelFilter::Warn)
+//!     .module("hyper", LevelFilter::Off)
+//!     .init()
+//!     .unwrap();
+//!
+//! log::error!("支付回调失败");
+//! ```
+//!
+//! # 注意
+//!
+//! 这个模块只在启用 `log` feature 时才可用。
+
+#![cfg(feature = "log")]
+
+use crate::worker::WORKER_THREAD_NAME;
+use crate::{BarkMessage, BarkWorker, Level, OverflowPolicy, SyncBarkClient, WorkerHandle};
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::time::Duration;
+
+/// 后台队列默认容量
+const DEFAULT_CAPACITY: usize = 256;
+
+/// [`Log::flush`] 等待队列中的推送发送完毕的最长时间
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 把日志记录转换为 Bark 推送的 `log::Log` 实现
+///
+/// 通过 [`BarkLogger::builder`] 创建。
+pub struct BarkLogger {
+    handle: WorkerHandle,
+    _worker: BarkWorker,
+    level: LevelFilter,
+    modules: Vec<(String, LevelFilter)>,
+    inner: Option<Box<dyn Log>>,
+}
+
+/// [`BarkLogger`] 构建器
+pub struct BarkLoggerBuilder {
+    client: SyncBarkClient,
+    level: LevelFilter,
+    modules: Vec<(String, LevelFilter)>,
+    capacity: usize,
+    inner: Option<Box<dyn Log>>,
+}
+
+impl BarkLogger {
+    /// 创建构建器
+    ///
+    /// 默认只推送 `Warn` 及以上级别的记录。
+    pub fn builder(client: SyncBarkClient) -> BarkLoggerBuilder {
+        BarkLoggerBuilder {
+            client,
+            level: LevelFilter::Warn,
+            modules: Vec::new(),
+            capacity: DEFAULT_CAPACITY,
+            inner: None,
+        }
+    }
+
+    /// 记录是否需要推送
+    fn should_push(&self, metadata: &Metadata<'_>) -> bool {
+        let target = metadata.target();
+        let level = self
+            .modules
+            .iter()
+            .filter(|(module, _)| {
+                target == module
+                    || target
+                        .strip_prefix(module.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.level, |(_, level)| *level);
+        metadata.level() <= level
+    }
+
+    /// 需要推送的最低级别，用于设置 `log::set_max_level`
+    fn max_level(&self) -> LevelFilter {
+        let own = self
+            .modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.level, std::cmp::max);
+        match &self.inner {
+            // 组合的日志实现可能需要更详细的记录
+            Some(_) => LevelFilter::Trace,
+            None => own,
+        }
+    }
+}
+
+impl Log for BarkLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.should_push(metadata)
+            || self
+                .inner
+                .as_ref()
+                .is_some_and(|inner| inner.enabled(metadata))
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if let Some(inner) = &self.inner {
+            if inner.enabled(record.metadata()) {
+                inner.log(record);
+            }
+        }
+
+        if !self.should_push(record.metadata()) {
+            return;
+        }
+        // 后台线程发送推送时产生的日志（如 HTTP 客户端的日志）不再推送，避免循环
+        if std::thread::current().name() == Some(WORKER_THREAD_NAME) {
+            return;
+        }
+
+        let mut body = record.args().to_string();
+        if let (Some(file), Some(line)) = (record.file(), record.line()) {
+            body.push_str(&format!("\n{}:{}", file, line));
+        }
+        let message = BarkMessage::builder()
+            .title(&format!("{} {}", record.level(), record.target()))
+            .body(&body)
+            .group(record.target())
+            .level(map_level(record.level()))
+            .build();
+        let _ = self.handle.enqueue(message);
+    }
+
+    /// 刷新组合的日志实现，并等待队列中的推送发送完毕（最多 5 秒）
+    fn flush(&self) {
+        if let Some(inner) = &self.inner {
+            inner.flush();
+        }
+        // 在后台线程中调用时等待自身会一直超时
+        if std::thread::current().name() != Some(WORKER_THREAD_NAME) {
+            self.handle.flush(FLUSH_TIMEOUT);
+        }
+    }
+}
+
+impl BarkLoggerBuilder {
+    /// 设置推送的最低级别，默认为 `Warn`
+    pub fn level(mut self, level: LevelFilter) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// 为某个模块及其子模块单独设置推送的最低级别
+    ///
+    /// 多个设置匹配同一个模块时，使用最长的模块路径。设置为 `LevelFilter::Off` 可以屏蔽某个模块。
+    pub fn module(mut self, module: &str, level: LevelFilter) -> Self {
+        self.modules.push((module.to_string(), level));
+        self
+    }
+
+    /// 设置后台队列容量，队列已满时新记录被丢弃
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// 组合已有的日志实现
+    ///
+    /// 所有记录都会先交给 `logger` 处理，与是否推送无关。
+    pub fn chain(mut self, logger: impl Log + 'static) -> Self {
+        self.inner = Some(Box::new(logger));
+        self
+    }
+
+    /// 启动后台线程并创建日志实现
+    pub fn build(self) -> BarkLogger {
+        let worker = BarkWorker::builder(self.client)
+            .capacity(self.capacity)
+            .overflow_policy(OverflowPolicy::DropNewest)
+            .spawn();
+
+        BarkLogger {
+            handle: worker.handle(),
+            _worker: worker,
+            level: self.level,
+            modules: self.modules,
+            inner: self.inner,
+        }
+    }
+
+    /// 创建日志实现并设置为全局日志
+    ///
+    /// 同时根据推送级别和组合的日志实现设置 `log::set_max_level`。
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        let logger = self.build();
+        let max_level = logger.max_level();
+        log::set_boxed_logger(Box::new(logger))?;
+        log::set_max_level(max_level);
+        Ok(())
+    }
+}
+
+/// 日志级别对应的推送级别
+fn map_level(level: log::Level) -> Level {
+    match level {
+        log::Level::Error => Level::Critical,
+        log::Level::Warn => Level::TimeSensitive,
+        log::Level::Info => Level::Active,
+        log::Level::Debug | log::Level::Trace => Level::Passive,
+    }
+}
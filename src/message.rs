@@ -17,7 +17,13 @@
 //!     .build();
 //! ```
 
-use serde::Deserialize;
+use crate::{ValidationError, ValidationIssue};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::str::FromStr;
+
+/// APNs 允许的最大负载字节数
+const MAX_PAYLOAD_SIZE: usize = 4096;
 
 /// 推送通知的级别
 ///
@@ -57,10 +63,125 @@ impl Level {
     }
 }
 
+impl std::fmt::Display for Level {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Level {
+    type Err = ParseLevelError;
+
+    /// 解析推送级别
+    ///
+    /// 除了 Bark API 使用的名称外，还接受大小写、`-`/`_` 分隔的写法以及常见别名：
+    /// `default`/`normal` 对应 [`Level::Active`]，`silent`/`quiet` 对应 [`Level::Passive`]。
+    ///
+    /// # 示例
+    ///
+    /// ```rust
+    /// use bark_rs::Level;
+    ///
+    /// assert_eq!("time-sensitive".parse::<Level>().unwrap(), Level::TimeSensitive);
+    /// assert_eq!("Critical".parse::<Level>().unwrap(), Level::Critical);
+    /// assert!("loud".parse::<Level>().is_err());
+    /// ```
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let normalized: String = value
+            .chars()
+            .filter(|c| !matches!(c, '-' | '_' | ' '))
+            .flat_map(char::to_lowercase)
+            .collect();
+
+        match normalized.as_str() {
+            "critical" => Ok(Level::Critical),
+            "active" | "default" | "normal" => Ok(Level::Active),
+            "timesensitive" => Ok(Level::TimeSensitive),
+            "passive" | "silent" | "quiet" => Ok(Level::Passive),
+            _ => Err(ParseLevelError(value.to_string())),
+        }
+    }
+}
+
+impl Serialize for Level {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Level {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// 无法识别的推送级别
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseLevelError(String);
+
+impl std::fmt::Display for ParseLevelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown level: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseLevelError {}
+
+/// 可以从客户端默认消息模板中补全的字段
+///
+/// 用于 [`BarkMessageBuilder::skip_default`]，让单条消息不使用模板中的某个字段。
+/// 参见 [`BarkMessage::merge_defaults`]。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MessageField {
+    /// 推送标题
+    Title,
+    /// 推送副标题
+    Subtitle,
+    /// 推送内容，包括 `body` 和 `markdown`
+    Body,
+    /// 设备密钥，包括 `device_key`、`device_keys` 和 `recipients`
+    DeviceKey,
+    /// 推送级别
+    Level,
+    /// 重要警告的音量
+    Volume,
+    /// 角标数字
+    Badge,
+    /// 是否重复播放铃声
+    Call,
+    /// 是否自动复制
+    AutoCopy,
+    /// 复制的内容
+    Copy,
+    /// 铃声
+    Sound,
+    /// 图标
+    Icon,
+    /// 图片
+    Image,
+    /// 消息分组
+    Group,
+    /// 是否保存消息
+    IsArchive,
+    /// 点击推送时跳转的 URL
+    Url,
+    /// 点击推送时的行为
+    Action,
+    /// 消息唯一标识
+    Id,
+    /// 是否删除消息
+    Delete,
+    /// 本库尚未支持的其他参数
+    Extra,
+}
+
 /// Bark API 响应结构
 ///
-/// 包含了 Bark 服务器返回的响应信息
-#[derive(Debug, Deserialize)]
+/// 包含了 Bark 服务器返回的成功响应信息。服务器返回的错误会被转换为
+/// [`BarkError::Api`](crate::BarkError::Api)，不会以 `BarkResponse` 的形式返回。
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BarkResponse {
     /// 响应状态码，200 表示成功
     pub code: i32,
@@ -70,6 +191,50 @@ pub struct BarkResponse {
 
     /// 可选的时间戳
     pub timestamp: Option<i64>,
+
+    /// 发送这条消息实际尝试的次数（不来自服务器）
+    ///
+    /// 参见 [`RetryPolicy`](crate::RetryPolicy)。
+    #[serde(skip)]
+    pub attempts: u32,
+}
+
+/// bark-server 的版本信息
+///
+/// 由 [`SyncBarkClient::info`](crate::SyncBarkClient::info) 返回，对应 `GET /info` 接口。
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ServerInfo {
+    /// 服务器版本号
+    pub version: String,
+
+    /// 构建时间
+    pub build: String,
+
+    /// 服务器运行的 CPU 架构
+    pub arch: String,
+
+    /// 构建所用的 Git 提交
+    pub commit: String,
+
+    /// 已注册的设备数量
+    pub devices: u64,
+}
+
+/// 设备注册结果
+///
+/// 由 [`SyncBarkClient::register`](crate::SyncBarkClient::register) 返回，对应 `POST /register` 接口。
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Registration {
+    /// 设备密钥，推送时使用
+    pub key: String,
+
+    /// 设备密钥（与 `key` 相同，兼容旧版服务器）
+    pub device_key: String,
+
+    /// APNs 设备令牌
+    pub device_token: String,
 }
 
 /// Bark 推送消息
@@ -104,67 +269,130 @@ pub struct BarkResponse {
 ///     .id("alert_001")
 ///     .build();
 /// ```
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct BarkMessage {
     /// 推送标题
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub title: Option<String>,
 
     /// 推送副标题
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub subtitle: Option<String>,
 
     /// 推送内容（必需）
     pub body: String,
 
+    /// Markdown 格式的推送内容
+    ///
+    /// 设置后 App 会渲染 Markdown 并忽略 `body`。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub markdown: Option<String>,
+
     /// 设备密钥（单个设备）
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub device_key: Option<String>,
 
     /// 设备密钥列表（批量推送）
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub device_keys: Option<Vec<String>>,
 
     /// 推送级别
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub level: Option<Level>,
 
-    /// 音量大小 (1-10)
+    /// 音量大小 (0-10)
+    #[serde(skip_serializing_if = "is_invalid_volume")]
     pub volume: Option<u8>,
 
     /// 应用角标数字
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub badge: Option<u32>,
 
     /// 是否重复播放铃声
+    #[serde(with = "flag", skip_serializing_if = "Option::is_none")]
     pub call: Option<bool>,
 
     /// 是否自动复制推送内容
+    #[serde(
+        rename = "autoCopy",
+        with = "flag",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub auto_copy: Option<bool>,
 
     /// 自定义复制内容
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub copy: Option<String>,
 
     /// 铃声名称
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub sound: Option<String>,
 
     /// 自定义图标 URL
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub icon: Option<String>,
 
+    /// 推送附带的图片 URL
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+
     /// 消息分组
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub group: Option<String>,
 
     /// 加密文本
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub ciphertext: Option<String>,
 
+    /// 加密使用的初始向量
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iv: Option<String>,
+
     /// 是否保存到历史
+    #[serde(
+        rename = "isArchive",
+        with = "flag",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub is_archive: Option<bool>,
 
     /// 点击跳转的 URL
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub url: Option<String>,
 
     /// 动作类型
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub action: Option<String>,
 
     /// 消息唯一标识
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
 
     /// 是否删除消息
+    #[serde(with = "flag", skip_serializing_if = "Option::is_none")]
     pub delete: Option<bool>,
+
+    /// 本库尚未支持的其他参数
+    ///
+    /// 原样合并到请求中；与上面的字段同名的参数会被忽略。反序列化时，
+    /// 所有无法识别的参数都会被放入这里。
+    #[serde(flatten, serialize_with = "serialize_extra")]
+    pub extra: Map<String, Value>,
+
+    /// 设备注册表中的设备名或设备组名（不会被发送）
+    ///
+    /// 发送时由客户端的 [`DeviceRegistry`](crate::DeviceRegistry) 解析为 `device_key` 或
+    /// `device_keys`，参见 [`BarkMessageBuilder::to`]。会被序列化，因此保存在
+    /// [`Outbox`](crate::Outbox) 中的消息重新打开后仍然发送给同样的设备。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recipients: Vec<String>,
+
+    /// 不从客户端默认消息模板中补全的字段（不会被发送）
+    ///
+    /// 参见 [`BarkMessage::merge_defaults`]。与 `recipients` 一样会被序列化。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skip_defaults: Vec<MessageField>,
 }
 
 impl BarkMessage {
@@ -181,6 +409,7 @@ impl BarkMessage {
     ///     .body("Hello World")
     ///     .build();
     /// ```
+    #[allow(clippy::new_ret_no_self)]
     pub fn new() -> BarkMessageBuilder {
         BarkMessageBuilder::new()
     }
@@ -203,31 +432,179 @@ impl BarkMessage {
     pub fn builder() -> BarkMessageBuilder {
         BarkMessageBuilder::new()
     }
-}
 
-impl Default for BarkMessage {
-    fn default() -> Self {
-        Self {
-            title: None,
-            subtitle: None,
-            body: String::new(),
-            device_key: None,
-            device_keys: None,
-            level: None,
-            volume: None,
-            badge: None,
-            call: None,
-            auto_copy: None,
-            copy: None,
-            sound: None,
-            icon: None,
-            group: None,
-            ciphertext: None,
-            is_archive: None,
-            url: None,
-            action: None,
-            id: None,
-            delete: None,
+    /// 校验消息参数
+    ///
+    /// 检查以下问题，并一次性返回发现的全部问题：
+    ///
+    /// - 没有设置 `body`、`markdown` 或 `ciphertext`
+    /// - 音量超出 0-10 的范围
+    /// - `url` 不是合法的 URL，`icon`/`image` 不是 `http`/`https` URL
+    /// - 同时设置了 `device_key` 和 `device_keys`
+    /// - 设置了 `delete` 但没有设置 `id`
+    /// - 消息超过了 APNs 4KB 的负载大小限制
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut issues = Vec::new();
+
+        if self.body.is_empty() && self.markdown.is_none() && self.ciphertext.is_none() {
+            issues.push(ValidationIssue::EmptyBody);
+        }
+
+        if let Some(volume) = self.volume.filter(|volume| *volume > 10) {
+            issues.push(ValidationIssue::VolumeOutOfRange(volume));
+        }
+
+        let links = [
+            ("url", &self.url, false),
+            ("icon", &self.icon, true),
+            ("image", &self.image, true),
+        ];
+        for (field, value, web_only) in links {
+            if let Some(value) = value {
+                if !is_valid_link(value, web_only) {
+                    issues.push(ValidationIssue::InvalidUrl {
+                        field,
+                        value: value.clone(),
+                    });
+                }
+            }
+        }
+
+        if self.device_key.is_some() && self.device_keys.is_some() {
+            issues.push(ValidationIssue::ConflictingDeviceKeys);
+        }
+
+        if self.delete == Some(true) && self.id.is_none() {
+            issues.push(ValidationIssue::DeleteWithoutId);
+        }
+
+        if let Ok(mut payload) = self.to_payload() {
+            payload.remove("device_keys");
+            let size = Value::Object(payload).to_string().len();
+            if size > MAX_PAYLOAD_SIZE {
+                issues.push(ValidationIssue::PayloadTooLarge {
+                    size,
+                    limit: MAX_PAYLOAD_SIZE,
+                });
+            }
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError { issues })
+        }
+    }
+
+    /// 用默认消息模板补全没有设置的字段
+    ///
+    /// 消息中已经设置的字段优先，列在 `skip_defaults` 中的字段不会被补全。其中：
+    ///
+    /// - `body` 和 `markdown` 只在消息没有任何内容（包括 `ciphertext`）时一起补全
+    /// - `device_key`、`device_keys` 和 `recipients` 只在消息没有指定任何设备时一起补全
+    /// - `extra` 按参数名逐项补全
+    /// - `ciphertext` 和 `iv` 不会被补全
+    ///
+    /// 客户端在发送前会用 [`with_defaults`](crate::SyncBarkClient::with_defaults)
+    /// 设置的模板调用这个方法。
+    ///
+    /// # 示例
+    ///
+    /// ```rust
+    /// use bark_rs::{BarkMessage, Level, MessageField};
+    ///
+    /// let defaults = BarkMessage::builder()
+    ///     .group("订单服务")
+    ///     .sound("bell")
+    ///     .level(Level::TimeSensitive)
+    ///     .build();
+    ///
+    /// let message = BarkMessage::builder()
+    ///     .body("支付回调超时")
+    ///     .level(Level::Critical)
+    ///     .skip_default(MessageField::Sound)
+    ///     .build()
+    ///     .merge_defaults(&defaults);
+    ///
+    /// assert_eq!(message.group.as_deref(), Some("订单服务"));
+    /// assert_eq!(message.level, Some(Level::Critical));
+    /// assert_eq!(message.sound, None);
+    /// ```
+    pub fn merge_defaults(&self, defaults: &BarkMessage) -> BarkMessage {
+        let mut message = self.clone();
+        let uses = |field| !self.skip_defaults.contains(&field);
+
+        macro_rules! fill {
+            ($($field:ident => $name:ident),* $(,)?) => {
+                $(
+                    if message.$field.is_none() && uses(MessageField::$name) {
+                        message.$field = defaults.$field.clone();
+                    }
+                )*
+            };
+        }
+        fill!(
+            title => Title,
+            subtitle => Subtitle,
+            level => Level,
+            volume => Volume,
+            badge => Badge,
+            call => Call,
+            auto_copy => AutoCopy,
+            copy => Copy,
+            sound => Sound,
+            icon => Icon,
+            image => Image,
+            group => Group,
+            is_archive => IsArchive,
+            url => Url,
+            action => Action,
+            id => Id,
+            delete => Delete,
+        );
+
+        let has_content =
+            !message.body.is_empty() || message.markdown.is_some() || message.ciphertext.is_some();
+        if !has_content && uses(MessageField::Body) {
+            message.body = defaults.body.clone();
+            message.markdown = defaults.markdown.clone();
+        }
+
+        if message.device_key.is_none()
+            && message.device_keys.is_none()
+            && message.recipients.is_empty()
+            && uses(MessageField::DeviceKey)
+        {
+            message.device_key = defaults.device_key.clone();
+            message.device_keys = defaults.device_keys.clone();
+            message.recipients = defaults.recipients.clone();
+        }
+
+        if uses(MessageField::Extra) {
+            for (key, value) in &defaults.extra {
+                if !message.extra.contains_key(key) {
+                    message.extra.insert(key.clone(), value.clone());
+                }
+            }
+        }
+
+        message
+    }
+
+    /// 构建发送给 Bark API 的 JSON 负载
+    ///
+    /// 即消息的序列化结果，保证与 `Serialize` 使用同一套字段名和取值格式。
+    /// 单个设备的 `device_key` 由客户端在发送时补充，因为它可能来自客户端的默认设备密钥；
+    /// 只在本地使用的 `recipients` 和 `skip_defaults` 不会发送。
+    pub(crate) fn to_payload(&self) -> serde_json::Result<Map<String, Value>> {
+        match serde_json::to_value(self)? {
+            Value::Object(mut payload) => {
+                payload.remove("device_key");
+                payload.remove("recipients");
+                payload.remove("skip_defaults");
+                Ok(payload)
+            }
+            _ => Err(serde::ser::Error::custom("message is not a JSON object")),
         }
     }
 }
@@ -248,6 +625,7 @@ impl Default for BarkMessage {
 ///     .volume(8)
 ///     .build();
 /// ```
+#[derive(Default)]
 pub struct BarkMessageBuilder {
     message: BarkMessage,
 }
@@ -282,6 +660,29 @@ impl BarkMessageBuilder {
         self
     }
 
+    /// 设置 Markdown 格式的推送内容
+    ///
+    /// 设置后 App 会渲染 Markdown 并忽略 [`body`](Self::body)，此时可以不设置 `body`。
+    ///
+    /// # 参数
+    ///
+    /// * `markdown` - Markdown 文本
+    ///
+    /// # 示例
+    ///
+    /// ```rust
+    /// use bark_rs::BarkMessage;
+    ///
+    /// let message = BarkMessage::builder()
+    ///     .title("部署完成")
+    ///     .markdown("**prod** 已更新到 `v1.2.0`")
+    ///     .build();
+    /// ```
+    pub fn markdown(mut self, markdown: &str) -> Self {
+        self.message.markdown = Some(markdown.to_string());
+        self
+    }
+
     /// 设置推送标题
     ///
     /// # 参数
@@ -356,15 +757,14 @@ impl BarkMessageBuilder {
 
     /// 设置铃声音量
     ///
-    /// 音量范围是 1-10，超出范围的值会被忽略。
+    /// 音量范围是 0-10，超出范围的值在 [`build()`](Self::build) 时会被忽略，
+    /// 在 [`try_build()`](Self::try_build) 时会报告为错误。
     ///
     /// # 参数
     ///
-    /// * `volume` - 音量大小 (1-10)
+    /// * `volume` - 音量大小 (0-10)
     pub fn volume(mut self, volume: u8) -> Self {
-        if volume <= 10 {
-            self.message.volume = Some(volume);
-        }
+        self.message.volume = Some(volume);
         self
     }
 
@@ -440,6 +840,18 @@ impl BarkMessageBuilder {
         self
     }
 
+    /// 设置推送附带的图片
+    ///
+    /// App 会下载图片并在通知中显示。
+    ///
+    /// # 参数
+    ///
+    /// * `image` - 图片 URL
+    pub fn image(mut self, image: &str) -> Self {
+        self.message.image = Some(image.to_string());
+        self
+    }
+
     /// 设置消息分组
     ///
     /// 相同分组的消息会被归类显示。
@@ -464,6 +876,19 @@ impl BarkMessageBuilder {
         self
     }
 
+    /// 设置加密使用的初始向量
+    ///
+    /// 与 [`ciphertext`](Self::ciphertext) 配合使用，告知 App 解密时使用的 IV。
+    /// 使用 [`BarkEncryptor`](crate::BarkEncryptor) 加密时会自动设置。
+    ///
+    /// # 参数
+    ///
+    /// * `iv` - 初始向量字符串
+    pub fn iv(mut self, iv: &str) -> Self {
+        self.message.iv = Some(iv.to_string());
+        self
+    }
+
     /// 设置是否保存到历史
     ///
     /// 当设置为 true 时，消息会被保存到历史记录中。
@@ -524,6 +949,82 @@ impl BarkMessageBuilder {
         self
     }
 
+    /// 设置本库尚未支持的参数
+    ///
+    /// Bark 新增参数后无需等待本库更新即可使用。参数原样合并到请求中，
+    /// 与已知参数同名的项会被忽略，请使用对应的方法设置。
+    ///
+    /// # 参数
+    ///
+    /// * `key` - 参数名
+    /// * `value` - 参数值，可以是字符串、数字或布尔值等任意 JSON 值
+    ///
+    /// # 示例
+    ///
+    /// ```rust
+    /// use bark_rs::BarkMessage;
+    ///
+    /// let message = BarkMessage::builder()
+    ///     .body("内容")
+    ///     .extra("newOption", "1")
+    ///     .extra("count", 3)
+    ///     .build();
+    /// assert_eq!(message.extra["count"], 3);
+    /// ```
+    pub fn extra(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.message.extra.insert(key.to_string(), value.into());
+        self
+    }
+
+    /// 按名称指定接收推送的设备或设备组
+    ///
+    /// 名称在发送时由客户端的 [`DeviceRegistry`](crate::DeviceRegistry) 解析为设备密钥，
+    /// 可以多次调用以指定多个设备或设备组。也可以同时使用 `device_key`/`device_keys`，
+    /// 推送会发送给所有这些设备。
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 设备名或设备组名
+    ///
+    /// # 示例
+    ///
+    /// ```rust
+    /// use bark_rs::BarkMessage;
+    ///
+    /// let message = BarkMessage::builder()
+    ///     .body("数据库主从延迟过高")
+    ///     .to("oncall")
+    ///     .to("db-team")
+    ///     .build();
+    /// ```
+    pub fn to(mut self, name: &str) -> Self {
+        self.message.recipients.push(name.to_string());
+        self
+    }
+
+    /// 不从客户端默认消息模板中补全某个字段
+    ///
+    /// 可以多次调用以排除多个字段，参见 [`BarkMessage::merge_defaults`]。
+    ///
+    /// # 示例
+    ///
+    /// ```rust
+    /// use bark_rs::{BarkMessage, MessageField};
+    ///
+    /// // 这条消息不使用模板中的分组和铃声
+    /// let message = BarkMessage::builder()
+    ///     .body("一次性通知")
+    ///     .skip_default(MessageField::Group)
+    ///     .skip_default(MessageField::Sound)
+    ///     .build();
+    /// ```
+    pub fn skip_default(mut self, field: MessageField) -> Self {
+        if !self.message.skip_defaults.contains(&field) {
+            self.message.skip_defaults.push(field);
+        }
+        self
+    }
+
     /// 构建最终的消息对象
     ///
     /// 完成消息构建并返回 [`BarkMessage`] 实例。
@@ -543,6 +1044,134 @@ impl BarkMessageBuilder {
     ///     .build();
     /// ```
     pub fn build(self) -> BarkMessage {
-        self.message
+        let mut message = self.message;
+        if message.volume.is_some_and(|volume| volume > 10) {
+            message.volume = None;
+        }
+        message
+    }
+
+    /// 校验并构建消息对象
+    ///
+    /// 与 [`build()`](Self::build) 不同，不会静默忽略不合法的参数，而是返回包含全部问题的
+    /// [`ValidationError`]。检查的内容参见 [`BarkMessage::validate`]。
+    ///
+    /// # 示例
+    ///
+    /// ```rust
+    /// use bark_rs::{BarkMessage, ValidationIssue};
+    ///
+    /// let error = BarkMessage::builder()
+    ///     .volume(11)
+    ///     .url("not a url")
+    ///     .try_build()
+    ///     .unwrap_err();
+    ///
+    /// assert_eq!(error.issues.len(), 3);
+    /// assert_eq!(error.issues[0], ValidationIssue::EmptyBody);
+    /// ```
+    pub fn try_build(self) -> Result<BarkMessage, ValidationError> {
+        self.message.validate()?;
+        Ok(self.message)
+    }
+}
+
+/// 检查 `url`/`icon`/`image` 参数是否为合法的链接
+///
+/// `url` 可以是任意协议（如 App 的 URL Scheme），图片链接必须是带主机名的 `http`/`https` URL。
+fn is_valid_link(value: &str, web_only: bool) -> bool {
+    match reqwest::Url::parse(value) {
+        Ok(url) if web_only => {
+            matches!(url.scheme(), "http" | "https")
+                && url.host_str().is_some_and(|h| !h.is_empty())
+        }
+        Ok(_) => true,
+        Err(_) => false,
+    }
+}
+
+/// Bark API 使用的参数名和只在本地使用的字段名，`extra` 中的同名参数会被忽略
+const KNOWN_KEYS: &[&str] = &[
+    "title",
+    "subtitle",
+    "body",
+    "markdown",
+    "device_key",
+    "device_keys",
+    "level",
+    "volume",
+    "badge",
+    "call",
+    "autoCopy",
+    "copy",
+    "sound",
+    "icon",
+    "image",
+    "group",
+    "ciphertext",
+    "iv",
+    "isArchive",
+    "url",
+    "action",
+    "id",
+    "delete",
+    "recipients",
+    "skip_defaults",
+];
+
+/// 超出 0-10 范围的音量不会发送
+fn is_invalid_volume(volume: &Option<u8>) -> bool {
+    !volume.is_some_and(|volume| volume <= 10)
+}
+
+/// 序列化 `extra`，跳过与已知参数同名的项
+fn serialize_extra<S: Serializer>(
+    extra: &Map<String, Value>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(
+        extra
+            .iter()
+            .filter(|(key, _)| !KNOWN_KEYS.contains(&key.as_str())),
+    )
+}
+
+/// Bark API 的开关参数
+///
+/// 序列化为 `"1"`/`"0"`；反序列化时同时接受字符串、数字和布尔值。
+mod flag {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<bool>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => serializer.serialize_str(if *value { "1" } else { "0" }),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<bool>, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Flag {
+            Bool(bool),
+            Number(u64),
+            Text(String),
+        }
+
+        match Option::<Flag>::deserialize(deserializer)? {
+            None => Ok(None),
+            Some(Flag::Bool(value)) => Ok(Some(value)),
+            Some(Flag::Number(value)) => Ok(Some(value != 0)),
+            Some(Flag::Text(value)) => match value.to_ascii_lowercase().as_str() {
+                "1" | "true" | "yes" => Ok(Some(true)),
+                "0" | "false" | "no" => Ok(Some(false)),
+                _ => Err(serde::de::Error::custom(format!("invalid flag: {}", value))),
+            },
+        }
     }
 }
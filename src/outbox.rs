@@ -0,0 +1,644 @@
+//! 持久化发件箱模块
+//!
+//! Bark 服务器不可达时，直接调用 `send` 的消息会丢失。[`Outbox`] 把消息以追加写入的日志
+//! 保存在指定目录中，网络恢复后按原始顺序重新发送，进程重启后也不会丢失。
+//! 每条消息可以设置过期时间，过期的告警在发送前会被丢弃。
+//! 通过设备注册表发送给多组设备的消息只有部分设备收到时，发件箱只保存尚未收到的设备，
+//! 重新发送时不会重复推送给已经收到的设备。
+//!
+//! 日志文件为 JSON Lines 格式，新消息追加写入并同步到磁盘；每次重新发送后日志会被重写为
+//! 只包含待发送消息的新日志（通过临时文件和重命名保证原子性）。同一个目录同时只能被一个进程使用。
+//!
+//! # 示例
+//!
+//! ```rust,no_run
+//! use bark_rs::{BarkMessage, Outbox, SyncBarkClient};
+//! use std::time::Duration;
+//!
+//! # fn example() -> bark_rs::Result<()> {
+//! let client = SyncBarkClient::with_device_key("https://api.day.app", "your_key");
+//! let outbox = Outbox::open("/var/lib/my-service/bark-outbox")?;
+//!
+//! // 发送失败时保存到发件箱，10 分钟后过期
+//! let message = BarkMessage::builder().body("磁盘空间不足").build();
+//! outbox.send(&client, &message, Some(Duration::from_secs(600)))?;
+//!
+//! // 定期重试
+//! let report = outbox.flush(&client)?;
+//! println!("发送 {} 条，剩余 {} 条", report.delivered, report.remaining);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::registry::{self, Route};
+use crate::{
+    BarkError, BarkMessage, BarkResponse, Result, SyncBarkClient, is_retryable_by_default,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "async")]
+use crate::AsyncBarkClient;
+
+/// 日志文件名
+const JOURNAL_FILE: &str = "journal.jsonl";
+
+/// 重写日志时使用的临时文件名
+const JOURNAL_TEMP_FILE: &str = "journal.jsonl.tmp";
+
+/// 发件箱中等待发送的消息，也是日志中的一行记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    id: u64,
+    created_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<u64>,
+    message: BarkMessage,
+    /// 部分设备已经收到推送时，尚未收到的设备密钥；为空时发送给 `message` 的所有设备
+    ///
+    /// 只保存设备密钥，服务器地址（可能包含认证凭据）在发送时重新从设备注册表解析。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pending_keys: Vec<String>,
+}
+
+impl Entry {
+    fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
+}
+
+/// 发件箱的内存状态
+struct Inner {
+    journal: File,
+    pending: VecDeque<Entry>,
+    next_id: u64,
+    flushing: bool,
+}
+
+/// 通过发件箱发送的结果
+#[derive(Debug)]
+pub enum OutboxStatus {
+    /// 已经发送成功
+    Sent(BarkResponse),
+
+    /// 暂时无法发送，已保存到发件箱
+    Queued {
+        /// 发件箱中的消息编号
+        id: u64,
+
+        /// 通过设备注册表发送给多组设备时，被服务器拒绝而不会再发送的消息及错误
+        ///
+        /// 消息是实际发送给被拒绝设备的请求，包含这些设备的密钥。
+        rejected: Vec<(BarkMessage, BarkError)>,
+    },
+}
+
+/// 一次重新发送的结果
+#[derive(Debug, Default)]
+pub struct FlushReport {
+    /// 发送成功的消息数量
+    pub delivered: usize,
+
+    /// 因为过期被丢弃的消息数量
+    pub expired: usize,
+
+    /// 被服务器拒绝（不可重试的错误）而丢弃的消息及错误
+    ///
+    /// 消息是实际发送给被拒绝设备的请求，包含这些设备的密钥。
+    pub rejected: Vec<(BarkMessage, BarkError)>,
+
+    /// 仍在发件箱中等待发送的消息数量
+    pub remaining: usize,
+
+    /// 导致本次重新发送提前停止的错误
+    pub last_error: Option<BarkError>,
+}
+
+/// 单条消息的发送结果
+enum Outcome {
+    Delivered,
+    Expired,
+    Rejected(Vec<(BarkMessage, BarkError)>),
+    Stop {
+        error: BarkError,
+        rejected: Vec<(BarkMessage, BarkError)>,
+        /// 有设备已经收到推送或被拒绝时，仍然需要发送的设备密钥
+        pending_keys: Option<Vec<String>>,
+    },
+}
+
+/// 持久化发件箱
+///
+/// 参见 [模块文档](self)。
+pub struct Outbox {
+    dir: PathBuf,
+    inner: Mutex<Inner>,
+}
+
+impl std::fmt::Debug for Outbox {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Outbox")
+            .field("dir", &self.dir)
+            .field("pending", &self.len())
+            .finish()
+    }
+}
+
+impl Outbox {
+    /// 打开发件箱目录，目录不存在时自动创建
+    ///
+    /// 会读取目录中已有的日志，恢复上次进程退出时尚未发送的消息。
+    /// 日志最后一行不完整的记录（例如写入时进程崩溃）会被截掉；其他位置的记录无法解析时
+    /// 说明日志已经损坏，返回错误而不是丢弃其中的消息。
+    ///
+    /// # 错误
+    ///
+    /// * [`BarkError::IoError`] - 无法创建目录、读写日志文件，或者日志已经损坏（[`ErrorKind::InvalidData`]）
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+
+        let path = dir.join(JOURNAL_FILE);
+        let mut pending = VecDeque::new();
+        let mut next_id = 1;
+        let content = match std::fs::read(&path) {
+            Ok(content) => content,
+            Err(error) if error.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(error) => return Err(error.into()),
+        };
+
+        let mut valid_len = 0;
+        let mut lines = content
+            .split_inclusive(|&byte| byte == b'\n')
+            .enumerate()
+            .peekable();
+        while let Some((index, line)) = lines.next() {
+            let record = line.trim_ascii();
+            if !record.is_empty() {
+                match serde_json::from_slice::<Entry>(record) {
+                    Ok(entry) => {
+                        next_id = next_id.max(entry.id + 1);
+                        pending.push_back(entry);
+                    }
+                    // 只有最后一行可能是写入时崩溃留下的
+                    Err(_) if lines.peek().is_none() => break,
+                    Err(error) => {
+                        return Err(std::io::Error::new(
+                            ErrorKind::InvalidData,
+                            format!(
+                                "corrupt outbox journal {} at line {}: {}",
+                                path.display(),
+                                index + 1,
+                                error
+                            ),
+                        )
+                        .into());
+                    }
+                }
+            }
+            valid_len += line.len();
+        }
+
+        let mut journal = OpenOptions::new().create(true).append(true).open(&path)?;
+        if valid_len < content.len() {
+            // 截掉不完整的最后一行，否则之后追加的记录会接在它后面
+            journal.set_len(valid_len as u64)?;
+            journal.sync_data()?;
+        } else if !content.is_empty() && !content.ends_with(b"\n") {
+            journal.write_all(b"\n")?;
+            journal.sync_data()?;
+        }
+        Ok(Self {
+            dir,
+            inner: Mutex::new(Inner {
+                journal,
+                pending,
+                next_id,
+                flushing: false,
+            }),
+        })
+    }
+
+    /// 发件箱所在的目录
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// 等待发送的消息数量
+    pub fn len(&self) -> usize {
+        self.lock().pending.len()
+    }
+
+    /// 发件箱是否为空
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 等待发送的消息，按加入顺序排列
+    pub fn pending(&self) -> Vec<BarkMessage> {
+        self.lock()
+            .pending
+            .iter()
+            .map(|entry| entry.message.clone())
+            .collect()
+    }
+
+    /// 将消息保存到发件箱，稍后通过 [`flush`](Self::flush) 发送
+    ///
+    /// 设置了 `ttl` 时，超过这个时间仍未发送的消息会被丢弃。返回消息在发件箱中的编号。
+    pub fn push(&self, message: &BarkMessage, ttl: Option<Duration>) -> Result<u64> {
+        self.push_pending(message, Vec::new(), ttl)
+    }
+
+    /// 将消息保存到发件箱，`pending_keys` 不为空时只发送给其中的设备
+    fn push_pending(
+        &self,
+        message: &BarkMessage,
+        pending_keys: Vec<String>,
+        ttl: Option<Duration>,
+    ) -> Result<u64> {
+        let now = now_millis();
+        let mut inner = self.lock();
+        let entry = Entry {
+            id: inner.next_id,
+            created_at: now,
+            expires_at: ttl.map(|ttl| now.saturating_add(ttl.as_millis() as u64)),
+            message: message.clone(),
+            pending_keys,
+        };
+        append(&mut inner.journal, std::slice::from_ref(&entry))?;
+        inner.next_id += 1;
+        inner.pending.push_back(entry);
+        Ok(inner.next_id - 1)
+    }
+
+    /// 发送消息，失败时保存到发件箱
+    ///
+    /// 会先尝试发送发件箱中积压的消息以保证顺序；积压的消息无法全部发送时，新消息直接保存到发件箱。
+    /// 网络错误等可重试的错误（参见 [`is_retryable_by_default`]）和认证失败会使消息被保存，
+    /// 返回 [`OutboxStatus::Queued`]；其他错误（如设备密钥无效）直接返回。
+    /// 通过设备注册表发送给多组设备时，只有暂时无法发送的设备会被保存；
+    /// 这时被服务器拒绝的设备不会再发送，记录在 [`OutboxStatus::Queued`] 的 `rejected` 中。
+    pub fn send(
+        &self,
+        client: &SyncBarkClient,
+        message: &BarkMessage,
+        ttl: Option<Duration>,
+    ) -> Result<OutboxStatus> {
+        if !self.is_empty() && self.flush(client)?.remaining > 0 {
+            return Ok(OutboxStatus::Queued {
+                id: self.push(message, ttl)?,
+                rejected: Vec::new(),
+            });
+        }
+
+        let routes = client.targets(message)?;
+        let results = routes
+            .iter()
+            .map(|route| client.send_route(route))
+            .collect();
+        self.settle(message, routes, results, ttl)
+    }
+
+    /// 按顺序重新发送发件箱中的消息
+    ///
+    /// 过期的消息被丢弃；被服务器拒绝的消息记录在 [`FlushReport::rejected`] 中并被丢弃，
+    /// 避免阻塞后面的消息；遇到可重试的错误时停止发送，剩余未过期的消息留待下次发送。
+    pub fn flush(&self, client: &SyncBarkClient) -> Result<FlushReport> {
+        let Some((_guard, entries)) = self.begin_flush() else {
+            return Ok(self.busy_report());
+        };
+
+        let mut outcomes = Vec::new();
+        let mut stopped = false;
+        for entry in &entries {
+            let outcome = if entry.is_expired(now_millis()) {
+                Outcome::Expired
+            } else if stopped {
+                continue;
+            } else {
+                match pending_routes(entry, client.targets(&entry.message)) {
+                    Ok(routes) => {
+                        let results = routes
+                            .iter()
+                            .map(|route| client.send_route(route))
+                            .collect();
+                        classify(routes, results)
+                    }
+                    Err(outcome) => outcome,
+                }
+            };
+            stopped |= matches!(outcome, Outcome::Stop { .. });
+            outcomes.push((entry.id, outcome));
+        }
+
+        self.finish_flush(outcomes)
+    }
+
+    /// 发送消息，失败时保存到发件箱
+    ///
+    /// [`send`](Self::send) 的异步版本。
+    #[cfg(feature = "async")]
+    pub async fn send_async(
+        &self,
+        client: &AsyncBarkClient,
+        message: &BarkMessage,
+        ttl: Option<Duration>,
+    ) -> Result<OutboxStatus> {
+        if !self.is_empty() && self.flush_async(client).await?.remaining > 0 {
+            return Ok(OutboxStatus::Queued {
+                id: self.push(message, ttl)?,
+                rejected: Vec::new(),
+            });
+        }
+
+        let routes = client.targets(message)?;
+        let mut results = Vec::with_capacity(routes.len());
+        for route in &routes {
+            results.push(client.send_route(route).await);
+        }
+        self.settle(message, routes, results, ttl)
+    }
+
+    /// 按顺序重新发送发件箱中的消息
+    ///
+    /// [`flush`](Self::flush) 的异步版本。
+    #[cfg(feature = "async")]
+    pub async fn flush_async(&self, client: &AsyncBarkClient) -> Result<FlushReport> {
+        let Some((_guard, entries)) = self.begin_flush() else {
+            return Ok(self.busy_report());
+        };
+
+        let mut outcomes = Vec::new();
+        let mut stopped = false;
+        for entry in &entries {
+            let outcome = if entry.is_expired(now_millis()) {
+                Outcome::Expired
+            } else if stopped {
+                continue;
+            } else {
+                match pending_routes(entry, client.targets(&entry.message)) {
+                    Ok(routes) => {
+                        let mut results = Vec::with_capacity(routes.len());
+                        for route in &routes {
+                            results.push(client.send_route(route).await);
+                        }
+                        classify(routes, results)
+                    }
+                    Err(outcome) => outcome,
+                }
+            };
+            stopped |= matches!(outcome, Outcome::Stop { .. });
+            outcomes.push((entry.id, outcome));
+        }
+
+        self.finish_flush(outcomes)
+    }
+
+    /// 处理直接发送的结果，暂时无法发送的设备保存到发件箱
+    ///
+    /// 没有任何设备收到推送或被拒绝时保存原始消息，否则只保存暂时无法发送的设备。
+    fn settle(
+        &self,
+        message: &BarkMessage,
+        routes: Vec<Route>,
+        results: Vec<Result<BarkResponse>>,
+        ttl: Option<Duration>,
+    ) -> Result<OutboxStatus> {
+        let mut pending = Vec::new();
+        let mut finished = Vec::new();
+        for (route, result) in routes.into_iter().zip(results) {
+            match result {
+                Err(error) if is_transient(&error) => pending.push(route),
+                result => finished.push((route, result)),
+            }
+        }
+
+        if pending.is_empty() {
+            let (routes, results): (Vec<_>, Vec<_>) = finished.into_iter().unzip();
+            return registry::combine(routes.iter().zip(results)).map(OutboxStatus::Sent);
+        }
+        let pending_keys = if finished.is_empty() {
+            Vec::new()
+        } else {
+            pending.iter().flat_map(Route::device_keys).collect()
+        };
+        let rejected = finished
+            .into_iter()
+            .filter_map(|(route, result)| result.err().map(|error| (route.message, error)))
+            .collect();
+        Ok(OutboxStatus::Queued {
+            id: self.push_pending(message, pending_keys, ttl)?,
+            rejected,
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// 取出待发送消息的快照；已有其他调用正在发送时返回 `None`
+    ///
+    /// 返回的 [`FlushGuard`] 在发送结束时清除发送中的标记。
+    fn begin_flush(&self) -> Option<(FlushGuard<'_>, Vec<Entry>)> {
+        let mut inner = self.lock();
+        if inner.flushing {
+            return None;
+        }
+        inner.flushing = true;
+        Some((FlushGuard(self), inner.pending.iter().cloned().collect()))
+    }
+
+    /// 其他调用正在发送时的结果
+    fn busy_report(&self) -> FlushReport {
+        FlushReport {
+            remaining: self.len(),
+            ..FlushReport::default()
+        }
+    }
+
+    /// 记录发送结果并压缩日志
+    fn finish_flush(&self, outcomes: Vec<(u64, Outcome)>) -> Result<FlushReport> {
+        let mut inner = self.lock();
+
+        let mut report = FlushReport::default();
+        let mut done = Vec::new();
+        let mut changed = false;
+        for (id, outcome) in outcomes {
+            match outcome {
+                Outcome::Delivered => report.delivered += 1,
+                Outcome::Expired => report.expired += 1,
+                Outcome::Rejected(rejected) => report.rejected.extend(rejected),
+                Outcome::Stop {
+                    error,
+                    rejected,
+                    pending_keys,
+                } => {
+                    report.rejected.extend(rejected);
+                    report.last_error = Some(error);
+                    if let Some(pending_keys) = pending_keys
+                        && let Some(entry) = inner.pending.iter_mut().find(|entry| entry.id == id)
+                    {
+                        entry.pending_keys = pending_keys;
+                        changed = true;
+                    }
+                    continue;
+                }
+            }
+            done.push(id);
+        }
+
+        inner.pending.retain(|entry| !done.contains(&entry.id));
+        report.remaining = inner.pending.len();
+        if !done.is_empty() || changed {
+            self.compact(&mut inner)?;
+        }
+        Ok(report)
+    }
+
+    /// 重写日志，只保留待发送的消息
+    fn compact(&self, inner: &mut Inner) -> Result<()> {
+        let path = self.dir.join(JOURNAL_FILE);
+        if inner.pending.is_empty() {
+            inner.journal.set_len(0)?;
+            inner.journal.sync_data()?;
+            return Ok(());
+        }
+
+        let temp_path = self.dir.join(JOURNAL_TEMP_FILE);
+        let mut temp = File::create(&temp_path)?;
+        append(&mut temp, inner.pending.make_contiguous())?;
+        std::fs::rename(&temp_path, &path)?;
+        sync_dir(&self.dir)?;
+        inner.journal = OpenOptions::new().append(true).open(&path)?;
+        Ok(())
+    }
+}
+
+/// 正在发送的标记
+///
+/// 丢弃时清除 `flushing`，`flush_async` 的 future 在发送中途被丢弃或发送时 panic，
+/// 发件箱也不会一直处于发送中的状态。
+struct FlushGuard<'a>(&'a Outbox);
+
+impl Drop for FlushGuard<'_> {
+    fn drop(&mut self) {
+        self.0.lock().flushing = false;
+    }
+}
+
+/// 消息中仍然需要发送的设备
+///
+/// `targets` 是客户端解析消息的结果。保存了尚未收到推送的设备时只发送给这些设备，
+/// 这些设备都已经不在设备注册表中时返回 [`BarkError::UnknownRecipient`]；解析失败时返回对应的结果。
+fn pending_routes(
+    entry: &Entry,
+    targets: Result<Vec<Route>>,
+) -> std::result::Result<Vec<Route>, Outcome> {
+    let routes = targets.map_err(|error| {
+        if is_transient(&error) {
+            Outcome::Stop {
+                error,
+                rejected: Vec::new(),
+                pending_keys: None,
+            }
+        } else {
+            Outcome::Rejected(vec![(entry.message.clone(), error)])
+        }
+    })?;
+    if entry.pending_keys.is_empty() {
+        return Ok(routes);
+    }
+
+    let routes: Vec<Route> = routes
+        .iter()
+        .filter_map(|route| route.only(&entry.pending_keys))
+        .collect();
+    if routes.is_empty() {
+        let error = BarkError::UnknownRecipient(entry.pending_keys.join(", "));
+        return Err(Outcome::Rejected(vec![(entry.message.clone(), error)]));
+    }
+    Ok(routes)
+}
+
+/// 判断一条消息发送给每组设备的结果
+///
+/// 有设备已经收到推送或被拒绝、其他设备暂时无法发送时，只保留暂时无法发送的设备。
+fn classify(routes: Vec<Route>, results: Vec<Result<BarkResponse>>) -> Outcome {
+    let attempted = routes.len();
+    let mut pending = Vec::new();
+    let mut rejected = Vec::new();
+    let mut last_error = None;
+    for (route, result) in routes.into_iter().zip(results) {
+        match result {
+            Ok(_) => {}
+            Err(error) if is_transient(&error) => {
+                last_error.get_or_insert(error);
+                pending.push(route);
+            }
+            Err(error) => rejected.push((route.message, error)),
+        }
+    }
+
+    match last_error {
+        None if rejected.is_empty() => Outcome::Delivered,
+        None => Outcome::Rejected(rejected),
+        Some(error) => Outcome::Stop {
+            error,
+            rejected,
+            pending_keys: (pending.len() < attempted)
+                .then(|| pending.iter().flat_map(Route::device_keys).collect()),
+        },
+    }
+}
+
+/// 是否为暂时性的错误，消息应当留在发件箱中稍后重试
+///
+/// 除了默认可重试的错误外，认证失败通常是配置问题，修复后消息仍然可以发送。
+fn is_transient(error: &BarkError) -> bool {
+    match error {
+        BarkError::RetriesExhausted { last_error, .. } => is_transient(last_error),
+        BarkError::Unauthorized => true,
+        error => is_retryable_by_default(error),
+    }
+}
+
+/// 追加写入记录并同步到磁盘
+fn append(file: &mut File, entries: &[Entry]) -> Result<()> {
+    let mut buffer = Vec::new();
+    for entry in entries {
+        serde_json::to_writer(&mut buffer, entry)?;
+        buffer.push(b'\n');
+    }
+    file.write_all(&buffer)?;
+    file.sync_data()?;
+    Ok(())
+}
+
+/// 同步目录，保证重命名在断电后仍然有效
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> Result<()> {
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+/// Windows 上无法打开目录进行同步，重命名由文件系统保证
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// 当前的 Unix 时间戳（毫秒）
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_millis() as u64)
+        .unwrap_or_default()
+}
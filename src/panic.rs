@@ -0,0 +1,222 @@
+//! panic 通知模块
+//!
+//! [`install_panic_hook`] 安装一个 panic hook，在进程崩溃前把 panic 信息推送出去：
+//! panic 消息、线程名称、代码位置、主机名和截断的调用栈，级别为 [`Level::Critical`]。
+//!
+//! 推送在单独的线程中同步发送，并最多等待 [`PanicHookOptions::timeout`]，
+//! 因此在 tokio 运行时的线程中 panic 时也不会因为 blocking 客户端而死锁。
+//! 原有的 panic hook 仍会被调用。
+//!
+//! # 示例
+//!
+//! ```rust,no_run
+//! use bark_rs::{PanicHookOptions, SyncBarkClient, install_panic_hook};
+//! use std::time::Duration;
+//!
+//! let client = SyncBarkClient::with_device_key("https://api.day.app", "your_key");
+//! install_panic_hook(
+//!     client,
+//!     PanicHookOptions::default()
+//!         .title("订单服务崩溃")
+//!         .timeout(Duration::from_secs(2)),
+//! );
+//! ```
+
+use crate::context::{hostname, program_name};
+use crate::{BarkMessage, Level, SyncBarkClient};
+use std::backtrace::Backtrace;
+use std::panic::PanicHookInfo;
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// 发送 panic 推送的线程名称
+const SENDER_THREAD_NAME: &str = "bark-panic-hook";
+
+/// panic hook 的选项
+#[derive(Debug, Clone)]
+pub struct PanicHookOptions {
+    /// 推送标题，默认为 `panic in <程序名>`
+    pub title: Option<String>,
+
+    /// 推送分组
+    pub group: Option<String>,
+
+    /// 等待推送发送完成的最长时间，默认为 3 秒
+    pub timeout: Duration,
+
+    /// 保留的调用栈帧数，为 0 时不捕获调用栈，默认为 20
+    ///
+    /// 按帧计数，每一帧包括函数名和源码位置；panic 机制和 hook 自身的帧不计入。
+    pub backtrace_lines: usize,
+}
+
+impl Default for PanicHookOptions {
+    fn default() -> Self {
+        Self {
+            title: None,
+            group: None,
+            timeout: Duration::from_secs(3),
+            backtrace_lines: 20,
+        }
+    }
+}
+
+impl PanicHookOptions {
+    /// 设置推送标题
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    /// 设置推送分组
+    pub fn group(mut self, group: &str) -> Self {
+        self.group = Some(group.to_string());
+        self
+    }
+
+    /// 设置等待推送发送完成的最长时间
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// 设置保留的调用栈帧数
+    pub fn backtrace_lines(mut self, lines: usize) -> Self {
+        self.backtrace_lines = lines;
+        self
+    }
+}
+
+/// 安装推送 panic 信息的 panic hook
+///
+/// 原有的 panic hook 会先被调用（默认会把 panic 信息打印到标准错误），然后发送推送，
+/// 最多等待 `options.timeout`。发送推送时再次发生的 panic 不会被推送。
+pub fn install_panic_hook(client: SyncBarkClient, options: PanicHookOptions) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        previous(info);
+
+        if std::thread::current().name() == Some(SENDER_THREAD_NAME) {
+            return;
+        }
+        let message = panic_message(info, &options);
+        let client = client.clone();
+        let (done, finished) = mpsc::channel();
+        let spawned = std::thread::Builder::new()
+            .name(SENDER_THREAD_NAME.to_string())
+            .spawn(move || {
+                let _ = done.send(client.send(&message));
+            });
+        if spawned.is_ok() {
+            let _ = finished.recv_timeout(options.timeout);
+        }
+    }));
+}
+
+/// 根据 panic 信息生成推送
+fn panic_message(info: &PanicHookInfo<'_>, options: &PanicHookOptions) -> BarkMessage {
+    let payload = info.payload();
+    let reason = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("Box<dyn Any>");
+
+    let thread = std::thread::current();
+    let mut body = format!(
+        "{}\nthread: {}",
+        reason,
+        thread.name().unwrap_or("<unnamed>")
+    );
+    if let Some(location) = info.location() {
+        body.push_str(&format!(
+            "\nlocation: {}:{}:{}",
+            location.file(),
+            location.line(),
+            location.column()
+        ));
+    }
+    if let Some(host) = hostname() {
+        body.push_str(&format!("\nhost: {}", host));
+    }
+    if options.backtrace_lines > 0 {
+        let backtrace = Backtrace::force_capture().to_string();
+        let frames = backtrace_frames(&backtrace);
+        if !frames.is_empty() {
+            body.push_str("\nbacktrace:\n");
+            body.push_str(&frames[..frames.len().min(options.backtrace_lines)].join("\n"));
+            if frames.len() > options.backtrace_lines {
+                body.push_str("\n...");
+            }
+        }
+    }
+    truncate(&mut body, MAX_BODY_SIZE);
+
+    let title = options
+        .title
+        .clone()
+        .unwrap_or_else(|| format!("panic in {}", program_name()));
+    let mut builder = BarkMessage::builder()
+        .title(&title)
+        .body(&body)
+        .level(Level::Critical);
+    if let Some(group) = &options.group {
+        builder = builder.group(group);
+    }
+    builder.build()
+}
+
+/// 把调用栈拆分为帧，去掉 panic 机制和 hook 自身的帧
+///
+/// 与标准库打印的短调用栈一样，从 `__rust_end_short_backtrace` 之后开始，
+/// 到 `__rust_begin_short_backtrace` 之前结束。没有这些标记时（如去掉了符号）
+/// 跳过最后一个属于 `std::panicking` 或本模块的帧之前的部分。
+pub(crate) fn backtrace_frames(backtrace: &str) -> Vec<String> {
+    let mut frames: Vec<String> = Vec::new();
+    for line in backtrace.lines() {
+        let is_frame = line.trim_start().split_once(':').is_some_and(|(index, _)| {
+            !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit())
+        });
+        match frames.last_mut() {
+            Some(frame) if !is_frame => {
+                frame.push('\n');
+                frame.push_str(line);
+            }
+            _ if is_frame => frames.push(line.to_string()),
+            _ => {}
+        }
+    }
+
+    let name = |frame: &String| frame.lines().next().unwrap_or_default().to_string();
+    let start = frames
+        .iter()
+        .rposition(|frame| name(frame).contains("__rust_end_short_backtrace"))
+        .or_else(|| {
+            frames.iter().rposition(|frame| {
+                let name = name(frame);
+                name.contains("std::panicking::") || name.contains("bark_rs::panic::")
+            })
+        })
+        .map_or(0, |index| index + 1);
+    let end = frames
+        .iter()
+        .position(|frame| name(frame).contains("__rust_begin_short_backtrace"))
+        .unwrap_or(frames.len())
+        .max(start);
+    frames.drain(start..end).collect()
+}
+
+/// 推送内容的最大字节数，避免超过 APNs 的负载限制
+pub(crate) const MAX_BODY_SIZE: usize = 3000;
+
+/// 在字符边界上截断字符串
+pub(crate) fn truncate(text: &mut String, limit: usize) {
+    if text.len() <= limit {
+        return;
+    }
+    let mut end = limit;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+}
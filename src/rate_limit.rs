@@ -0,0 +1,703 @@
+//! 客户端限流模块
+//!
+//! 这个模块提供了在发送前限制推送频率的 [`RateLimiter`]，避免异常的检查任务在短时间内
+//! 发出大量推送。限流可以按设备密钥、按 `group` 和全局三个范围分别配置：
+//!
+//! - [`RateLimit`]：令牌桶，允许短时间内的突发，长期平均速率不超过设定值
+//! - [`Quota`]：固定窗口的配额（如每天最多 100 条），窗口按 UTC 时间对齐
+//!
+//! 超出配额的消息可以直接丢弃，也可以在窗口重置后合并为一条
+//! "N notifications suppressed" 的汇总推送，参见 [`QuotaOverflow`]。
+//! 汇总推送没有后台定时器，只在下一次 `send` 或调用
+//! [`flush_summaries`](RateLimitedClient::flush_summaries) 时发出；
+//! 窗口重置后可能不再有新消息的服务需要定期调用 `flush_summaries`。
+//!
+//! [`RateLimitedClient`] 和 `AsyncRateLimitedClient`（需要启用 `async` feature）
+//! 分别包装同步和异步客户端。被限流的消息不会发送，`send` 返回
+//! [`BarkError::Suppressed`](crate::BarkError::Suppressed)。
+//!
+//! # 示例
+//!
+//! ```rust,no_run
+//! use bark_rs::{Quota, QuotaOverflow, RateLimit, RateLimitedClient, RateLimiter, SyncBarkClient};
+//!
+//! # fn example() -> bark_rs::Result<()> {
+//! let limiter = RateLimiter::builder()
+//!     .device_rate(RateLimit::per_minute(10))
+//!     .group_rate(RateLimit::per_minute(3))
+//!     .device_quota(Quota::daily(200))
+//!     .quota_overflow(QuotaOverflow::Summarize)
+//!     .build();
+//!
+//! let client = SyncBarkClient::with_device_key("https://api.day.app", "your_key");
+//! let client = RateLimitedClient::new(client, limiter.clone());
+//!
+//! client.send(&bark_rs::BarkMessage::builder().body("磁盘空间不足").build())?;
+//!
+//! for budget in limiter.budgets() {
+//!     println!("{}: 今日剩余 {:?}", budget.scope, budget.quota_remaining);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::registry::Route;
+use crate::{BarkError, BarkMessage, BarkResponse, Result, Suppression, SyncBarkClient};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "async")]
+use crate::AsyncBarkClient;
+
+/// 令牌桶限流参数
+///
+/// 桶中最多保存 `burst` 个令牌，每 `period` 时间补充 `burst` 个令牌（均匀补充）。
+/// 每发送一条消息消耗一个令牌，没有令牌时消息被限流。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// 桶容量，也就是允许的最大突发数量
+    pub burst: u32,
+
+    /// 补满整个桶所需的时间
+    pub period: Duration,
+}
+
+impl RateLimit {
+    /// 每 `period` 时间最多发送 `burst` 条消息
+    pub fn new(burst: u32, period: Duration) -> Self {
+        Self { burst, period }
+    }
+
+    /// 每秒最多发送 `count` 条消息
+    pub fn per_second(count: u32) -> Self {
+        Self::new(count, Duration::from_secs(1))
+    }
+
+    /// 每分钟最多发送 `count` 条消息
+    pub fn per_minute(count: u32) -> Self {
+        Self::new(count, Duration::from_secs(60))
+    }
+
+    /// 每小时最多发送 `count` 条消息
+    pub fn per_hour(count: u32) -> Self {
+        Self::new(count, Duration::from_secs(3600))
+    }
+
+    /// 经过 `elapsed` 时间补充的令牌数量
+    fn refill(&self, elapsed: Duration) -> f64 {
+        if self.period.is_zero() {
+            return f64::from(self.burst);
+        }
+        elapsed.as_secs_f64() / self.period.as_secs_f64() * f64::from(self.burst)
+    }
+
+    /// 从 `tokens` 个令牌补充到一个令牌需要等待的时间
+    fn wait_for_token(&self, tokens: f64) -> Duration {
+        if self.burst == 0 {
+            return self.period;
+        }
+        self.period
+            .mul_f64(((1.0 - tokens) / f64::from(self.burst)).max(0.0))
+    }
+}
+
+/// 固定窗口配额
+///
+/// 每个窗口内最多发送 `limit` 条消息。窗口从 Unix 纪元开始按 `window` 对齐，
+/// 因此 [`Quota::daily`] 的窗口在每天 UTC 零点重置。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    /// 每个窗口允许发送的消息数量
+    pub limit: u32,
+
+    /// 窗口长度
+    pub window: Duration,
+}
+
+impl Quota {
+    /// 每个 `window` 时间窗口最多发送 `limit` 条消息
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Self { limit, window }
+    }
+
+    /// 每天（UTC）最多发送 `limit` 条消息
+    pub fn daily(limit: u32) -> Self {
+        Self::new(limit, Duration::from_secs(24 * 3600))
+    }
+
+    /// `now` 所在窗口的序号
+    fn window_index(&self, now: Duration) -> u128 {
+        now.as_millis() / self.window.as_millis().max(1)
+    }
+
+    /// 从 `now` 到窗口重置的时间
+    fn resets_in(&self, now: Duration) -> Duration {
+        let window = self.window.as_millis().max(1);
+        let elapsed = now.as_millis() % window;
+        Duration::from_millis((window - elapsed) as u64)
+    }
+}
+
+/// 超出配额时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuotaOverflow {
+    /// 直接丢弃超出配额的消息
+    #[default]
+    Drop,
+
+    /// 丢弃超出配额的消息，并在窗口重置后发送一条汇总推送，说明有多少条消息被丢弃
+    ///
+    /// 汇总推送在窗口重置后的下一次发送前，或者调用
+    /// [`RateLimitedClient::flush_summaries`] 时发出，不会由定时器自动发送。
+    /// 汇总推送发送给最后一条被丢弃的消息的设备；通过设备注册表发送给使用其他服务器的设备时，
+    /// 汇总推送也发送到那个服务器。
+    Summarize,
+}
+
+/// 限流的范围
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Scope {
+    /// 所有消息
+    Global,
+
+    /// 发送给某个设备的消息
+    Device(String),
+
+    /// 属于某个 `group` 的消息
+    Group(String),
+}
+
+impl std::fmt::Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scope::Global => write!(f, "global"),
+            Scope::Device(key) => write!(f, "device {}", key),
+            Scope::Group(group) => write!(f, "group {}", group),
+        }
+    }
+}
+
+/// 某个范围当前的限流状态
+#[derive(Debug, Clone, PartialEq)]
+pub struct Budget {
+    /// 限流的范围
+    pub scope: Scope,
+
+    /// 令牌桶中可用的令牌数量，没有配置 [`RateLimit`] 时为 `None`
+    pub tokens: Option<u32>,
+
+    /// 当前窗口已发送的消息数量
+    pub quota_used: u32,
+
+    /// 当前窗口剩余的配额，没有配置 [`Quota`] 时为 `None`
+    pub quota_remaining: Option<u32>,
+
+    /// 距离配额窗口重置的时间，没有配置 [`Quota`] 时为 `None`
+    pub resets_in: Option<Duration>,
+
+    /// 当前窗口因超出配额被丢弃的消息数量
+    pub quota_exceeded: u32,
+
+    /// 累计因令牌不足被限流的消息数量
+    pub rate_limited: u64,
+}
+
+/// 某一类范围的限流配置
+#[derive(Debug, Clone, Copy, Default)]
+struct Limits {
+    rate: Option<RateLimit>,
+    quota: Option<Quota>,
+}
+
+/// 令牌桶状态
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Duration,
+}
+
+/// 配额窗口状态
+#[derive(Debug)]
+struct Usage {
+    window: u128,
+    used: u32,
+    exceeded: u32,
+
+    /// 最后一条超出配额的消息，用于生成汇总推送的服务器、目标设备和分组
+    last_exceeded: Option<Route>,
+}
+
+#[derive(Debug, Default)]
+struct ScopeState {
+    bucket: Option<Bucket>,
+    usage: Option<Usage>,
+    rate_limited: u64,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    scopes: HashMap<Scope, ScopeState>,
+
+    /// 等待发送的汇总推送
+    summaries: Vec<Route>,
+}
+
+#[derive(Debug)]
+struct Config {
+    global: Limits,
+    device: Limits,
+    group: Limits,
+    overflow: QuotaOverflow,
+}
+
+impl Config {
+    fn limits(&self, scope: &Scope) -> Limits {
+        match scope {
+            Scope::Global => self.global,
+            Scope::Device(_) => self.device,
+            Scope::Group(_) => self.group,
+        }
+    }
+}
+
+/// 推送限流器
+///
+/// 克隆的限流器共享同一份状态，因此可以同时用于同步和异步客户端，
+/// 或者保留一份用于查看当前的限流状态。
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    config: Arc<Config>,
+    state: Arc<Mutex<State>>,
+}
+
+impl RateLimiter {
+    /// 创建限流器构建器
+    pub fn builder() -> RateLimiterBuilder {
+        RateLimiterBuilder::default()
+    }
+
+    /// 查看某个范围当前的限流状态
+    pub fn budget(&self, scope: &Scope) -> Budget {
+        let now = now();
+        let mut state = self.lock();
+        self.refresh(&mut state, scope, now);
+        self.snapshot(&state, scope, now)
+    }
+
+    /// 查看所有已经发送过消息的范围的限流状态，按范围排序
+    pub fn budgets(&self) -> Vec<Budget> {
+        let now = now();
+        let mut state = self.lock();
+        let mut scopes: Vec<Scope> = state.scopes.keys().cloned().collect();
+        scopes.sort();
+        scopes
+            .iter()
+            .map(|scope| {
+                self.refresh(&mut state, scope, now);
+                self.snapshot(&state, scope, now)
+            })
+            .collect()
+    }
+
+    /// 尝试为消息获取发送许可
+    ///
+    /// `targets` 是一次发送实际发出的请求，通过设备注册表发送给多组设备时有多条。
+    /// 消息涉及的所有范围都有剩余额度时才会消耗额度并返回成功；
+    /// 否则不消耗任何额度，返回第一个被限流的范围。
+    pub(crate) fn acquire(
+        &self,
+        targets: &[Route],
+        default_device_key: Option<&str>,
+    ) -> std::result::Result<(), Suppression> {
+        self.acquire_at(targets, default_device_key, now())
+    }
+
+    pub(crate) fn acquire_at(
+        &self,
+        targets: &[Route],
+        default_device_key: Option<&str>,
+        now: Duration,
+    ) -> std::result::Result<(), Suppression> {
+        let scopes = scopes(targets, default_device_key);
+        let mut state = self.lock();
+        for (scope, _) in &scopes {
+            self.refresh(&mut state, scope, now);
+        }
+
+        for (scope, target) in &scopes {
+            let limits = self.config.limits(scope);
+            let entry = state.scopes.entry(scope.clone()).or_default();
+
+            if let (Some(rate), Some(bucket)) = (limits.rate, &entry.bucket) {
+                if bucket.tokens < 1.0 {
+                    entry.rate_limited += 1;
+                    return Err(Suppression::RateLimited {
+                        scope: scope.clone(),
+                        retry_after: rate.wait_for_token(bucket.tokens),
+                    });
+                }
+            }
+
+            if let (Some(quota), Some(usage)) = (limits.quota, &mut entry.usage) {
+                if usage.used >= quota.limit {
+                    usage.exceeded += 1;
+                    usage.last_exceeded = Some((*target).clone());
+                    return Err(Suppression::QuotaExceeded {
+                        scope: scope.clone(),
+                        resets_in: quota.resets_in(now),
+                    });
+                }
+            }
+        }
+
+        for (scope, _) in &scopes {
+            let entry = state.scopes.entry(scope.clone()).or_default();
+            if let Some(bucket) = &mut entry.bucket {
+                bucket.tokens -= 1.0;
+            }
+            if let Some(usage) = &mut entry.usage {
+                usage.used += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// 取出窗口已经重置、等待发送的汇总推送
+    pub(crate) fn take_summaries(&self) -> Vec<Route> {
+        self.take_summaries_at(now())
+    }
+
+    pub(crate) fn take_summaries_at(&self, now: Duration) -> Vec<Route> {
+        let mut state = self.lock();
+        let scopes: Vec<Scope> = state.scopes.keys().cloned().collect();
+        for scope in &scopes {
+            self.refresh(&mut state, scope, now);
+        }
+        std::mem::take(&mut state.summaries)
+    }
+
+    /// 补充令牌并滚动配额窗口
+    fn refresh(&self, state: &mut State, scope: &Scope, now: Duration) {
+        let limits = self.config.limits(scope);
+        if limits.rate.is_none() && limits.quota.is_none() {
+            return;
+        }
+
+        let entry = state.scopes.entry(scope.clone()).or_default();
+        if let Some(rate) = limits.rate {
+            let bucket = entry.bucket.get_or_insert(Bucket {
+                tokens: f64::from(rate.burst),
+                updated: now,
+            });
+            let elapsed = now.saturating_sub(bucket.updated);
+            bucket.tokens = (bucket.tokens + rate.refill(elapsed)).min(f64::from(rate.burst));
+            bucket.updated = bucket.updated.max(now);
+        }
+
+        let Some(quota) = limits.quota else {
+            return;
+        };
+        let window = quota.window_index(now);
+        let usage = entry.usage.get_or_insert(Usage {
+            window,
+            used: 0,
+            exceeded: 0,
+            last_exceeded: None,
+        });
+        if usage.window >= window {
+            return;
+        }
+
+        let exceeded = usage.exceeded;
+        let last_exceeded = usage.last_exceeded.take();
+        *usage = Usage {
+            window,
+            used: 0,
+            exceeded: 0,
+            last_exceeded: None,
+        };
+        if self.config.overflow == QuotaOverflow::Summarize {
+            if let Some(last) = last_exceeded {
+                state.summaries.push(summary(scope, &last, exceeded));
+            }
+        }
+    }
+
+    fn snapshot(&self, state: &State, scope: &Scope, now: Duration) -> Budget {
+        let limits = self.config.limits(scope);
+        let entry = state.scopes.get(scope);
+        let usage = entry.and_then(|entry| entry.usage.as_ref());
+        let quota_used = usage.map_or(0, |usage| usage.used);
+
+        Budget {
+            scope: scope.clone(),
+            tokens: entry
+                .and_then(|entry| entry.bucket.as_ref())
+                .map(|bucket| bucket.tokens.floor() as u32),
+            quota_used,
+            quota_remaining: limits
+                .quota
+                .map(|quota| quota.limit.saturating_sub(quota_used)),
+            resets_in: limits.quota.map(|quota| quota.resets_in(now)),
+            quota_exceeded: usage.map_or(0, |usage| usage.exceeded),
+            rate_limited: entry.map_or(0, |entry| entry.rate_limited),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// [`RateLimiter`] 构建器
+///
+/// 没有配置的范围不受限制。
+#[derive(Debug, Default)]
+pub struct RateLimiterBuilder {
+    global: Limits,
+    device: Limits,
+    group: Limits,
+    overflow: QuotaOverflow,
+}
+
+impl RateLimiterBuilder {
+    /// 限制所有消息的发送频率
+    pub fn global_rate(mut self, rate: RateLimit) -> Self {
+        self.global.rate = Some(rate);
+        self
+    }
+
+    /// 限制发送给每个设备的频率
+    ///
+    /// 批量推送的消息会消耗每个目标设备的额度。
+    pub fn device_rate(mut self, rate: RateLimit) -> Self {
+        self.device.rate = Some(rate);
+        self
+    }
+
+    /// 限制每个 `group` 的发送频率，没有设置 `group` 的消息不受限制
+    pub fn group_rate(mut self, rate: RateLimit) -> Self {
+        self.group.rate = Some(rate);
+        self
+    }
+
+    /// 设置所有消息的配额
+    pub fn global_quota(mut self, quota: Quota) -> Self {
+        self.global.quota = Some(quota);
+        self
+    }
+
+    /// 设置每个设备的配额
+    pub fn device_quota(mut self, quota: Quota) -> Self {
+        self.device.quota = Some(quota);
+        self
+    }
+
+    /// 设置每个 `group` 的配额
+    pub fn group_quota(mut self, quota: Quota) -> Self {
+        self.group.quota = Some(quota);
+        self
+    }
+
+    /// 设置超出配额时的处理方式，默认直接丢弃
+    pub fn quota_overflow(mut self, overflow: QuotaOverflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// 创建限流器
+    pub fn build(self) -> RateLimiter {
+        RateLimiter {
+            config: Arc::new(Config {
+                global: self.global,
+                device: self.device,
+                group: self.group,
+                overflow: self.overflow,
+            }),
+            state: Arc::new(Mutex::new(State::default())),
+        }
+    }
+}
+
+/// 带限流的同步客户端
+///
+/// 发送前检查 [`RateLimiter`]，被限流的消息不会发送，返回
+/// [`BarkError::Suppressed`]。等待发送的汇总推送会在下一次发送前或调用
+/// [`flush_summaries`](Self::flush_summaries) 时发出，汇总推送不受限流。
+/// 分组和目标设备取自补全了客户端默认消息模板和上下文、解析了收件人的消息。
+///
+/// 客户端不会启动后台线程，窗口重置后没有新消息时汇总推送会一直等待，
+/// 需要汇总推送及时送达时应当定期调用 `flush_summaries`：
+///
+/// ```rust,no_run
+/// # use bark_rs::RateLimitedClient;
+/// # fn example(client: RateLimitedClient) {
+/// std::thread::spawn(move || loop {
+///     std::thread::sleep(std::time::Duration::from_secs(60));
+///     client.flush_summaries();
+/// });
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RateLimitedClient {
+    client: SyncBarkClient,
+    limiter: RateLimiter,
+}
+
+impl RateLimitedClient {
+    /// 使用限流器包装客户端
+    pub fn new(client: SyncBarkClient, limiter: RateLimiter) -> Self {
+        Self { client, limiter }
+    }
+
+    /// 被包装的客户端
+    pub fn client(&self) -> &SyncBarkClient {
+        &self.client
+    }
+
+    /// 使用的限流器
+    pub fn limiter(&self) -> &RateLimiter {
+        &self.limiter
+    }
+
+    /// 在限流允许时发送消息
+    ///
+    /// 发送前会先发出等待中的汇总推送，汇总推送的发送结果不影响这条消息。
+    pub fn send(&self, message: &BarkMessage) -> Result<BarkResponse> {
+        self.flush_summaries();
+        let targets = self.client.targets(message)?;
+        self.limiter
+            .acquire(&targets, self.client.default_device_key.as_deref())?;
+        self.client.send(message)
+    }
+
+    /// 发送窗口已经重置、等待发送的汇总推送
+    ///
+    /// 返回每条汇总推送的发送结果，没有等待发送的汇总推送时返回空列表。
+    pub fn flush_summaries(&self) -> Vec<Result<BarkResponse>> {
+        self.limiter
+            .take_summaries()
+            .iter()
+            .map(|summary| self.client.send_route(summary))
+            .collect()
+    }
+}
+
+/// 带限流的异步客户端
+///
+/// [`RateLimitedClient`] 的异步版本。同样不会自动发送汇总推送，
+/// 需要时在定时任务中调用 [`flush_summaries`](Self::flush_summaries)。
+#[cfg(feature = "async")]
+#[derive(Debug, Clone)]
+pub struct AsyncRateLimitedClient {
+    client: AsyncBarkClient,
+    limiter: RateLimiter,
+}
+
+#[cfg(feature = "async")]
+impl AsyncRateLimitedClient {
+    /// 使用限流器包装客户端
+    pub fn new(client: AsyncBarkClient, limiter: RateLimiter) -> Self {
+        Self { client, limiter }
+    }
+
+    /// 被包装的客户端
+    pub fn client(&self) -> &AsyncBarkClient {
+        &self.client
+    }
+
+    /// 使用的限流器
+    pub fn limiter(&self) -> &RateLimiter {
+        &self.limiter
+    }
+
+    /// 在限流允许时发送消息
+    ///
+    /// 发送前会先发出等待中的汇总推送，汇总推送的发送结果不影响这条消息。
+    pub async fn send(&self, message: &BarkMessage) -> Result<BarkResponse> {
+        self.flush_summaries().await;
+        let targets = self.client.targets(message)?;
+        self.limiter
+            .acquire(&targets, self.client.default_device_key.as_deref())?;
+        self.client.send(message).await
+    }
+
+    /// 发送窗口已经重置、等待发送的汇总推送
+    pub async fn flush_summaries(&self) -> Vec<Result<BarkResponse>> {
+        let mut results = Vec::new();
+        for summary in self.limiter.take_summaries() {
+            results.push(self.client.send_route(&summary).await);
+        }
+        results
+    }
+}
+
+impl From<Suppression> for BarkError {
+    fn from(suppression: Suppression) -> Self {
+        BarkError::Suppressed(suppression)
+    }
+}
+
+/// 消息涉及的限流范围：目标设备、分组和全局
+///
+/// 每个范围只出现一次，并附带第一条涉及它的消息，用于生成汇总推送。
+fn scopes<'m>(targets: &'m [Route], default_device_key: Option<&str>) -> Vec<(Scope, &'m Route)> {
+    let mut scopes: Vec<(Scope, &Route)> = Vec::new();
+    let mut add = |scope: Scope, target| {
+        if !scopes.iter().any(|(existing, _)| *existing == scope) {
+            scopes.push((scope, target));
+        }
+    };
+    for target in targets {
+        let message = &target.message;
+        let devices: Vec<Scope> = match (&message.device_keys, &message.device_key) {
+            (Some(keys), _) => keys.iter().cloned().map(Scope::Device).collect(),
+            (None, Some(key)) => vec![Scope::Device(key.clone())],
+            (None, None) => default_device_key
+                .map(|key| Scope::Device(key.to_string()))
+                .into_iter()
+                .collect(),
+        };
+        for scope in devices {
+            add(scope, target);
+        }
+        if let Some(group) = &message.group {
+            add(Scope::Group(group.clone()), target);
+        }
+    }
+    if let Some(first) = targets.first() {
+        add(Scope::Global, first);
+    }
+    scopes
+}
+
+/// 生成说明有多少条消息被丢弃的汇总推送
+///
+/// 服务器、发送目标和分组与最后一条被丢弃的消息相同。通过设备注册表发送给多组设备时，
+/// 取涉及这个范围的第一组设备。
+fn summary(scope: &Scope, last: &Route, count: u32) -> Route {
+    let mut message = BarkMessage::builder()
+        .body(&format!("{} notifications suppressed", count))
+        .build();
+    match scope {
+        Scope::Device(key) => message.device_key = Some(key.clone()),
+        _ => {
+            message.device_key = last.message.device_key.clone();
+            message.device_keys = last.message.device_keys.clone();
+        }
+    }
+    message.group = last.message.group.clone();
+    Route {
+        server: last.server.clone(),
+        message,
+    }
+}
+
+fn now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
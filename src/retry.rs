@@ -0,0 +1,264 @@
+//! 重试策略模块
+//!
+//! 这个模块提供了发送失败时的自动重试策略：指数退避、可选的随机抖动、
+//! 对 `Retry-After` 响应头的支持，以及可自定义的「哪些错误可以重试」判断。
+//!
+//! # 示例
+//!
+//! ```rust
+//! use bark_rs::{RetryPolicy, SyncBarkClient};
+//! use std::time::Duration;
+//!
+//! let policy = RetryPolicy::new(5)
+//!     .base_delay(Duration::from_millis(500))
+//!     .max_delay(Duration::from_secs(30));
+//!
+//! let client = SyncBarkClient::with_device_key("https://api.day.app", "key")
+//!     .with_retry_policy(policy);
+//! ```
+
+use crate::{ApiErrorKind, BarkError};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 判断错误是否可以重试的函数
+type RetryPredicate = Arc<dyn Fn(&BarkError) -> bool + Send + Sync>;
+
+/// 发送失败时的重试策略
+///
+/// 第 `n` 次重试前等待 `base_delay * 2^(n-1)`，不超过 `max_delay`。启用抖动时，
+/// 实际等待时间在计算结果的一半到全部之间随机选取，避免大量客户端同时重试。
+///
+/// 如果服务器返回了 `Retry-After` 响应头（通常伴随 429 或 503），会按照服务器要求的时间等待；
+/// 要求的时间超过 `max_delay` 时不再重试，直接返回错误。
+///
+/// 默认只有网络错误、传输层错误、429 和 5xx 响应会被重试，可以通过
+/// [`retry_if`](Self::retry_if) 自定义。
+#[derive(Clone)]
+pub struct RetryPolicy {
+    /// 最大尝试次数（包括第一次发送）
+    max_attempts: u32,
+
+    /// 第一次重试前的等待时间
+    base_delay: Duration,
+
+    /// 单次等待的最长时间
+    max_delay: Duration,
+
+    /// 是否启用随机抖动
+    jitter: bool,
+
+    /// 判断错误是否可以重试
+    retryable: RetryPredicate,
+}
+
+impl std::fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("base_delay", &self.base_delay)
+            .field("max_delay", &self.max_delay)
+            .field("jitter", &self.jitter)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for RetryPolicy {
+    /// 默认策略只尝试一次，即不重试
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl RetryPolicy {
+    /// 创建最多尝试 `max_attempts` 次的重试策略
+    ///
+    /// 默认第一次重试前等待 200 毫秒，单次等待不超过 10 秒，并启用随机抖动。
+    ///
+    /// # 参数
+    ///
+    /// * `max_attempts` - 最大尝试次数（包括第一次发送），小于 1 时按 1 处理
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(10),
+            jitter: true,
+            retryable: Arc::new(is_retryable_by_default),
+        }
+    }
+
+    /// 不重试的策略，客户端的默认值
+    pub fn none() -> Self {
+        Self::new(1)
+    }
+
+    /// 设置第一次重试前的等待时间
+    pub fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    /// 设置单次等待的最长时间
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// 设置是否启用随机抖动
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// 自定义哪些错误可以重试
+    ///
+    /// # 示例
+    ///
+    /// ```rust
+    /// use bark_rs::{ApiErrorKind, BarkError, RetryPolicy};
+    ///
+    /// // 只重试服务器错误
+    /// let policy = RetryPolicy::new(3).retry_if(|error| {
+    ///     matches!(error, BarkError::Api(e) if e.kind == ApiErrorKind::ServerError)
+    /// });
+    /// ```
+    pub fn retry_if<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&BarkError) -> bool + Send + Sync + 'static,
+    {
+        self.retryable = Arc::new(predicate);
+        self
+    }
+
+    /// 获取最大尝试次数
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// 判断错误是否可以重试
+    pub fn is_retryable(&self, error: &BarkError) -> bool {
+        (self.retryable)(error)
+    }
+
+    /// 计算第 `attempt` 次尝试失败后的等待时间
+    ///
+    /// 返回 `None` 表示不应再重试：错误不可重试、已达到最大尝试次数，
+    /// 或服务器要求的等待时间超过了 `max_delay`。
+    pub(crate) fn next_delay(&self, attempt: u32, error: &BarkError) -> Option<Duration> {
+        if attempt >= self.max_attempts || !self.is_retryable(error) {
+            return None;
+        }
+
+        if let BarkError::Api(api) = error {
+            if let Some(retry_after) = api.retry_after {
+                return (retry_after <= self.max_delay).then_some(retry_after);
+            }
+        }
+
+        let factor = 2u32.saturating_pow(attempt - 1);
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        if self.jitter {
+            let half = delay / 2;
+            Some(half + half.mul_f64(random_fraction()))
+        } else {
+            Some(delay)
+        }
+    }
+
+    /// 放弃重试时返回的错误
+    ///
+    /// 经过重试后仍然因为可重试的错误失败时，包装为 [`BarkError::RetriesExhausted`]；
+    /// 其他情况原样返回错误。
+    pub(crate) fn give_up(&self, attempts: u32, error: BarkError) -> BarkError {
+        if attempts > 1 && self.is_retryable(&error) {
+            BarkError::RetriesExhausted {
+                attempts,
+                last_error: Box::new(error),
+            }
+        } else {
+            error
+        }
+    }
+}
+
+/// 默认的可重试判断：网络错误、传输层错误、429 和 5xx 响应
+pub fn is_retryable_by_default(error: &BarkError) -> bool {
+    match error {
+        BarkError::RequestError(_) | BarkError::TransportError(_) => true,
+        BarkError::Api(e) => matches!(
+            e.kind,
+            ApiErrorKind::RateLimited | ApiErrorKind::ServerError
+        ),
+        BarkError::InvalidResponse { status, .. } => *status == 429 || *status >= 500,
+        _ => false,
+    }
+}
+
+/// 返回 `[0, 1)` 之间的随机数
+fn random_fraction() -> f64 {
+    let mut buf = [0u8; 8];
+    if getrandom::fill(&mut buf).is_err() {
+        return 0.5;
+    }
+    (u64::from_le_bytes(buf) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// 解析 `Retry-After` 响应头
+///
+/// 支持秒数（`120`）和 HTTP 日期（`Wed, 21 Oct 2015 07:28:00 GMT`）两种格式。
+pub(crate) fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let target = parse_http_date(value)?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    Some(Duration::from_secs(target.saturating_sub(now)))
+}
+
+/// 将 IMF-fixdate 格式的 HTTP 日期解析为 Unix 时间戳
+pub(crate) fn parse_http_date(value: &str) -> Option<u64> {
+    // Wed, 21 Oct 2015 07:28:00 GMT
+    let mut parts = value.split_whitespace();
+    let _weekday = parts.next()?;
+    let day: u64 = parts.next()?.parse().ok()?;
+    let month = match parts.next()? {
+        "Jan" => 1,
+        "Feb" => 2,
+        "Mar" => 3,
+        "Apr" => 4,
+        "May" => 5,
+        "Jun" => 6,
+        "Jul" => 7,
+        "Aug" => 8,
+        "Sep" => 9,
+        "Oct" => 10,
+        "Nov" => 11,
+        "Dec" => 12,
+        _ => return None,
+    };
+    let year: u64 = parts.next()?.parse().ok()?;
+    let mut time = parts.next()?.split(':');
+    let hour: u64 = time.next()?.parse().ok()?;
+    let minute: u64 = time.next()?.parse().ok()?;
+    let second: u64 = time.next()?.parse().ok()?;
+    if parts.next()? != "GMT" || year < 1970 {
+        return None;
+    }
+
+    // 按公历计算自 1970-01-01 起的天数
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era_days = 365 * y + y / 4 - y / 100 + y / 400;
+    let days = era_days + (153 * m + 2) / 5 + day - 1 - 719_468;
+
+    Some(days * 86_400 + hour * 3_600 + minute * 60 + second)
+}
@@ -0,0 +1,195 @@
+//! 测试用的本地 HTTP 服务器
+//!
+//! 基于标准库的 `TcpListener` 实现，按顺序返回预设的响应并记录收到的请求，
+//! 用于在不访问真实 Bark 服务器的情况下测试客户端行为。
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+/// 预设的响应
+#[derive(Debug, Clone)]
+pub(crate) struct Reply {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Reply {
+    pub fn json(status: u16, body: &str) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+            body: body.to_string(),
+        }
+    }
+
+    pub fn success() -> Self {
+        Self::json(
+            200,
+            r#"{"code":200,"message":"success","timestamp":1700000000}"#,
+        )
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// 服务器收到的请求
+#[derive(Debug, Clone)]
+pub(crate) struct Received {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Received {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body).expect("request body is not json")
+    }
+}
+
+/// 本地测试服务器
+///
+/// 预设的响应用完之后一律返回成功响应。
+pub(crate) struct TestServer {
+    addr: SocketAddr,
+    received: Arc<Mutex<Vec<Received>>>,
+    stopped: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl TestServer {
+    pub fn start(replies: Vec<Reply>) -> Self {
+        Self::start_on("127.0.0.1:0".parse().unwrap(), replies)
+    }
+
+    /// 在指定地址上启动，用于模拟服务器停止后恢复
+    pub fn start_on(addr: SocketAddr, replies: Vec<Reply>) -> Self {
+        let listener = TcpListener::bind(addr).expect("failed to bind test server");
+        let addr = listener.local_addr().unwrap();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let handle = {
+            let received = received.clone();
+            let stopped = stopped.clone();
+            let mut replies = replies.into_iter();
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stopped.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let Ok(mut stream) = stream else { continue };
+                    if let Some(request) = read_request(&mut stream) {
+                        received.lock().unwrap().push(request);
+                        let reply = replies.next().unwrap_or_else(Reply::success);
+                        write_reply(&mut stream, &reply);
+                    }
+                }
+            })
+        };
+
+        Self {
+            addr,
+            received,
+            stopped,
+            handle: Some(handle),
+        }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn received(&self) -> Vec<Received> {
+        self.received.lock().unwrap().clone()
+    }
+
+    /// 停止服务器，之后的连接会被拒绝
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // 唤醒阻塞在 accept 上的线程
+        let _ = TcpStream::connect(self.addr);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn read_request(stream: &mut TcpStream) -> Option<Received> {
+    let mut reader = BufReader::new(stream.try_clone().ok()?);
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let path = parts.next()?.to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    let length = headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+
+    Some(Received {
+        method,
+        path,
+        headers,
+        body,
+    })
+}
+
+fn write_reply(stream: &mut TcpStream, reply: &Reply) {
+    let mut response = format!("HTTP/1.1 {} Test\r\n", reply.status);
+    for (name, value) in &reply.headers {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    response.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        reply.body.len(),
+        reply.body
+    ));
+    let _ = stream.write_all(response.as_bytes());
+    let _ = stream.flush();
+}
@@ -0,0 +1,309 @@
+//! tracing 集成模块
+//!
+//! [`BarkLayer`] 实现了 `tracing_subscriber::Layer`，把达到指定级别的 tracing 事件转换为
+//! Bark 推送：
+//!
+//! - 级别：`ERROR` → [`Level::Critical`]，`WARN` → [`Level::TimeSensitive`]，
+//!   `INFO` → [`Level::Active`]，`DEBUG`/`TRACE` → [`Level::Passive`]
+//! - 标题：事件所在的 span 路径（如 `request > db_query`），没有 span 时为事件的 target
+//! - 分组：事件的 target
+//! - 内容：事件的消息和字段，以及所在 span 的字段
+//!
+//! 推送通过内部的 [`BarkWorker`] 在后台线程中发送，队列已满时直接丢弃新事件，
+//! 因此记录日志永远不会等待网络。同一个调用位置的事件在时间间隔内只推送一次，
+//! 被跳过的事件数量会附加在下一条推送中。
+//!
+//! # 示例
+//!
+//! ```rust,no_run
+//! use bark_rs::{BarkLayer, SyncBarkClient};
+//! use tracing_subscriber::layer::SubscriberExt;
+//!
+//! let client = SyncBarkClient::with_device_key("https://api.day.app", "your_key");
+//! let layer = BarkLayer::builder(client)
+//!     .min_level(tracing::Level::ERROR)
+//!     .build();
+//!
+//! let handle = layer.handle();
+//! let subscriber = tracing_subscriber::registry().with(layer);
+//! tracing::subscriber::set_global_default(subscriber).unwrap();
+//!
+//! tracing::error!(order_id = 42, "支付回调失败");
+//!
+//! // 退出前等待队列中的推送发送完毕
+//! handle.flush(std::time::Duration::from_secs(5));
+//! ```
+//!
+//! # 注意
+//!
+//! 这个模块只在启用 `tracing` feature 时才可用。
+
+#![cfg(feature = "tracing")]
+
+use crate::worker::WORKER_THREAD_NAME;
+use crate::{BarkMessage, BarkWorker, Level, OverflowPolicy, SyncBarkClient, WorkerHandle};
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tracing::callsite::Identifier;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// 后台队列默认容量
+const DEFAULT_CAPACITY: usize = 256;
+
+/// 同一个调用位置默认的最短推送间隔
+const DEFAULT_CALLSITE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 把 tracing 事件转换为 Bark 推送的 Layer
+///
+/// 通过 [`BarkLayer::builder`] 创建。被丢弃时后台队列会被关闭，剩余的推送仍会在后台线程中发送。
+pub struct BarkLayer {
+    handle: WorkerHandle,
+    _worker: BarkWorker,
+    min_level: tracing::Level,
+    callsite_interval: Duration,
+    callsites: Mutex<HashMap<Identifier, Callsite>>,
+}
+
+/// [`BarkLayer`] 构建器
+pub struct BarkLayerBuilder {
+    client: SyncBarkClient,
+    min_level: tracing::Level,
+    capacity: usize,
+    callsite_interval: Duration,
+}
+
+/// 调用位置的限流状态
+struct Callsite {
+    last_sent: Instant,
+    skipped: usize,
+}
+
+/// 保存在 span 扩展中的字段
+struct SpanFields(String);
+
+impl BarkLayer {
+    /// 创建构建器
+    ///
+    /// 默认只推送 `WARN` 及以上级别的事件，同一个调用位置每分钟最多推送一次。
+    pub fn builder(client: SyncBarkClient) -> BarkLayerBuilder {
+        BarkLayerBuilder {
+            client,
+            min_level: tracing::Level::WARN,
+            capacity: DEFAULT_CAPACITY,
+            callsite_interval: DEFAULT_CALLSITE_INTERVAL,
+        }
+    }
+
+    /// 后台队列的句柄
+    ///
+    /// Layer 安装到 subscriber 后就无法再访问，可以在安装前保存句柄，
+    /// 进程退出前调用 [`WorkerHandle::flush`] 等待队列中的推送发送完毕。
+    pub fn handle(&self) -> WorkerHandle {
+        self.handle.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<Identifier, Callsite>> {
+        self.callsites
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// 检查调用位置的限流，允许推送时返回之前被跳过的事件数量
+    fn admit(&self, callsite: Identifier) -> Option<usize> {
+        let now = Instant::now();
+        let mut callsites = self.lock();
+        match callsites.get_mut(&callsite) {
+            Some(state) if now.duration_since(state.last_sent) < self.callsite_interval => {
+                state.skipped += 1;
+                None
+            }
+            Some(state) => {
+                state.last_sent = now;
+                Some(std::mem::take(&mut state.skipped))
+            }
+            None => {
+                callsites.insert(
+                    callsite,
+                    Callsite {
+                        last_sent: now,
+                        skipped: 0,
+                    },
+                );
+                Some(0)
+            }
+        }
+    }
+
+    /// 推送未能放入队列时把跳过的事件数量（包括这一次）还给调用位置
+    fn restore(&self, callsite: Identifier, skipped: usize) {
+        if let Some(state) = self.lock().get_mut(&callsite) {
+            state.skipped += skipped + 1;
+        }
+    }
+}
+
+impl BarkLayerBuilder {
+    /// 设置推送的最低级别，默认为 `WARN`
+    pub fn min_level(mut self, level: tracing::Level) -> Self {
+        self.min_level = level;
+        self
+    }
+
+    /// 设置后台队列容量，队列已满时新事件被丢弃
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// 设置同一个调用位置的最短推送间隔，默认为 60 秒
+    pub fn callsite_interval(mut self, interval: Duration) -> Self {
+        self.callsite_interval = interval;
+        self
+    }
+
+    /// 启动后台线程并创建 Layer
+    pub fn build(self) -> BarkLayer {
+        let worker = BarkWorker::builder(self.client)
+            .capacity(self.capacity)
+            .overflow_policy(OverflowPolicy::DropNewest)
+            .spawn();
+
+        BarkLayer {
+            handle: worker.handle(),
+            _worker: worker,
+            min_level: self.min_level,
+            callsite_interval: self.callsite_interval,
+            callsites: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<S> Layer<S> for BarkLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut visitor = FieldVisitor::default();
+        attrs.record(&mut visitor);
+        span.extensions_mut()
+            .insert(SpanFields(visitor.fields.join(" ")));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut visitor = FieldVisitor::default();
+        values.record(&mut visitor);
+        let mut extensions = span.extensions_mut();
+        match extensions.get_mut::<SpanFields>() {
+            Some(SpanFields(fields)) => {
+                for field in visitor.fields {
+                    if !fields.is_empty() {
+                        fields.push(' ');
+                    }
+                    fields.push_str(&field);
+                }
+            }
+            None => extensions.insert(SpanFields(visitor.fields.join(" "))),
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if *metadata.level() > self.min_level {
+            return;
+        }
+        // 后台线程发送推送时产生的事件（如 HTTP 客户端的日志）不再推送，避免循环
+        if std::thread::current().name() == Some(WORKER_THREAD_NAME) {
+            return;
+        }
+        let Some(skipped) = self.admit(metadata.callsite()) else {
+            return;
+        };
+
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let mut body = visitor.message;
+        if !visitor.fields.is_empty() {
+            if !body.is_empty() {
+                body.push('\n');
+            }
+            body.push_str(&visitor.fields.join(" "));
+        }
+
+        let mut spans = Vec::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if let Some(SpanFields(fields)) = span.extensions().get::<SpanFields>() {
+                    if !fields.is_empty() {
+                        let _ = write!(body, "\n{}: {}", span.name(), fields);
+                    }
+                }
+                spans.push(span.name());
+            }
+        }
+        if skipped > 0 {
+            let _ = write!(body, "\n({} similar events skipped)", skipped);
+        }
+
+        let title = if spans.is_empty() {
+            metadata.target().to_string()
+        } else {
+            spans.join(" > ")
+        };
+        let message = BarkMessage::builder()
+            .title(&title)
+            .body(&body)
+            .group(metadata.target())
+            .level(map_level(*metadata.level()))
+            .build();
+        if self.handle.enqueue(message).is_err() {
+            self.restore(metadata.callsite(), skipped);
+        }
+    }
+}
+
+/// tracing 级别对应的推送级别
+fn map_level(level: tracing::Level) -> Level {
+    match level {
+        tracing::Level::ERROR => Level::Critical,
+        tracing::Level::WARN => Level::TimeSensitive,
+        tracing::Level::INFO => Level::Active,
+        _ => Level::Passive,
+    }
+}
+
+/// 收集事件消息和字段
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    fields: Vec<String>,
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.fields.push(format!("{}={}", field.name(), value));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.fields.push(format!("{}={:?}", field.name(), value));
+        }
+    }
+}
@@ -0,0 +1,497 @@
+//! 后台推送模块
+//!
+//! 这个模块提供了在后台线程中发送推送的 [`BarkWorker`]：调用方只需把消息放入有界队列，
+//! 不必等待 Bark 服务器响应。队列已满时的行为由 [`OverflowPolicy`] 决定，
+//! 关闭时会在截止时间内尽量发送完队列中的消息，并通过 [`ShutdownReport`] 报告结果。
+//!
+//! 异步版本参见 `AsyncBarkWorker`（需要启用 `async` feature）。
+//!
+//! # 示例
+//!
+//! ```rust,no_run
+//! use bark_rs::{BarkMessage, BarkWorker, OverflowPolicy, SyncBarkClient};
+//! use std::time::Duration;
+//!
+//! let client = SyncBarkClient::with_device_key("https://api.day.app", "your_key");
+//! let worker = BarkWorker::builder(client)
+//!     .capacity(1000)
+//!     .overflow_policy(OverflowPolicy::DropOldest)
+//!     .spawn();
+//!
+//! let handle = worker.handle();
+//! handle.enqueue(BarkMessage::builder().body("订单已创建").build()).ok();
+//!
+//! let report = worker.shutdown(Duration::from_secs(5));
+//! println!("发送 {} 条，丢弃 {} 条", report.delivered, report.dropped + report.unsent.len());
+//! ```
+
+use crate::{BarkError, BarkMessage, SyncBarkClient};
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// 队列默认容量
+pub const DEFAULT_QUEUE_CAPACITY: usize = 1024;
+
+/// 后台线程的名称
+pub(crate) const WORKER_THREAD_NAME: &str = "bark-worker";
+
+/// 发送失败时的回调
+pub(crate) type FailureHandler = Arc<dyn Fn(&BarkMessage, &BarkError) + Send + Sync>;
+
+/// 队列已满时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// 等待队列有空位
+    #[default]
+    Block,
+
+    /// 丢弃新消息，`enqueue` 返回 [`EnqueueError::Full`]
+    DropNewest,
+
+    /// 丢弃队列中最早的消息，为新消息腾出位置
+    DropOldest,
+}
+
+/// 消息无法放入队列
+#[derive(Debug)]
+pub enum EnqueueError {
+    /// 队列已满且策略为 [`OverflowPolicy::DropNewest`]
+    Full(Box<BarkMessage>),
+
+    /// 后台任务已经关闭
+    Closed(Box<BarkMessage>),
+}
+
+impl EnqueueError {
+    /// 取回未能放入队列的消息
+    pub fn into_message(self) -> BarkMessage {
+        match self {
+            EnqueueError::Full(message) | EnqueueError::Closed(message) => *message,
+        }
+    }
+}
+
+impl std::fmt::Display for EnqueueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EnqueueError::Full(_) => write!(f, "queue is full"),
+            EnqueueError::Closed(_) => write!(f, "worker is shut down"),
+        }
+    }
+}
+
+impl std::error::Error for EnqueueError {}
+
+/// 关闭后台任务的结果
+#[derive(Debug, Default)]
+pub struct ShutdownReport {
+    /// 发送成功的消息数量
+    pub delivered: usize,
+
+    /// 发送失败的消息数量
+    pub failed: usize,
+
+    /// 因为队列已满被丢弃的消息数量
+    pub dropped: usize,
+
+    /// 截止时间到达时仍未发送的消息
+    pub unsent: Vec<BarkMessage>,
+
+    /// 截止时间到达时正在发送、结果未知的消息
+    ///
+    /// 它可能已经送达，也可能在关闭后失败，不计入 `delivered` 或 `failed`。
+    pub in_flight: Option<BarkMessage>,
+}
+
+/// 同步和异步后台任务共享的队列状态
+#[derive(Default)]
+pub(crate) struct QueueState {
+    messages: VecDeque<BarkMessage>,
+    in_flight: Option<BarkMessage>,
+    closed: bool,
+    deadline: Option<Instant>,
+    finished: bool,
+    delivered: usize,
+    failed: usize,
+    dropped: usize,
+}
+
+/// 入队结果
+#[allow(clippy::large_enum_variant)]
+pub(crate) enum Push {
+    /// 已放入队列
+    Queued,
+
+    /// 队列已满，调用方需要等待后重试
+    Wait(BarkMessage),
+
+    /// 无法放入队列
+    Rejected(EnqueueError),
+}
+
+/// 出队结果
+#[allow(clippy::large_enum_variant)]
+pub(crate) enum Pop {
+    /// 取出一条待发送的消息
+    Message(BarkMessage),
+
+    /// 队列为空，等待新消息
+    Wait,
+
+    /// 已关闭且没有需要发送的消息，或者已过截止时间
+    Exit,
+}
+
+impl QueueState {
+    pub(crate) fn push(
+        &mut self,
+        message: BarkMessage,
+        capacity: usize,
+        policy: OverflowPolicy,
+    ) -> Push {
+        if self.closed {
+            return Push::Rejected(EnqueueError::Closed(Box::new(message)));
+        }
+
+        if self.messages.len() >= capacity {
+            match policy {
+                OverflowPolicy::Block => return Push::Wait(message),
+                OverflowPolicy::DropNewest => {
+                    self.dropped += 1;
+                    return Push::Rejected(EnqueueError::Full(Box::new(message)));
+                }
+                OverflowPolicy::DropOldest => {
+                    self.messages.pop_front();
+                    self.dropped += 1;
+                }
+            }
+        }
+
+        self.messages.push_back(message);
+        Push::Queued
+    }
+
+    pub(crate) fn pop(&mut self) -> Pop {
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Pop::Exit;
+        }
+
+        match self.messages.pop_front() {
+            Some(message) => {
+                self.in_flight = Some(message.clone());
+                Pop::Message(message)
+            }
+            None if self.closed => Pop::Exit,
+            None => Pop::Wait,
+        }
+    }
+
+    /// 记录一条消息的发送结果
+    pub(crate) fn record(&mut self, success: bool) {
+        self.in_flight = None;
+        if success {
+            self.delivered += 1;
+        } else {
+            self.failed += 1;
+        }
+    }
+
+    pub(crate) fn close(&mut self, deadline: Option<Instant>) {
+        self.closed = true;
+        if deadline.is_some() {
+            self.deadline = deadline;
+        }
+    }
+
+    pub(crate) fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// 队列为空且没有正在发送的消息
+    pub(crate) fn is_idle(&self) -> bool {
+        self.messages.is_empty() && self.in_flight.is_none()
+    }
+
+    pub(crate) fn report(&mut self) -> ShutdownReport {
+        ShutdownReport {
+            delivered: self.delivered,
+            failed: self.failed,
+            dropped: self.dropped,
+            unsent: self.messages.drain(..).collect(),
+            in_flight: self.in_flight.take(),
+        }
+    }
+}
+
+/// 同步后台任务的共享状态
+struct Shared {
+    state: Mutex<QueueState>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    finished: Condvar,
+    idle: Condvar,
+    capacity: usize,
+    policy: OverflowPolicy,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// 在后台线程中发送推送
+///
+/// 通过 [`BarkWorker::builder`] 创建。[`handle()`](Self::handle) 返回的 [`WorkerHandle`]
+/// 可以廉价地克隆并在多个线程中使用。
+///
+/// 不调用 [`shutdown`](Self::shutdown) 直接丢弃时，队列会被关闭，
+/// 剩余的消息仍会在后台线程中发送完毕。
+pub struct BarkWorker {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// 向后台任务提交消息的句柄
+#[derive(Clone)]
+pub struct WorkerHandle {
+    shared: Arc<Shared>,
+}
+
+/// [`BarkWorker`] 构建器
+pub struct BarkWorkerBuilder {
+    client: SyncBarkClient,
+    capacity: usize,
+    policy: OverflowPolicy,
+    on_failure: Option<FailureHandler>,
+}
+
+impl BarkWorker {
+    /// 创建后台任务构建器
+    ///
+    /// 默认队列容量为 [`DEFAULT_QUEUE_CAPACITY`]，队列已满时等待。
+    pub fn builder(client: SyncBarkClient) -> BarkWorkerBuilder {
+        BarkWorkerBuilder {
+            client,
+            capacity: DEFAULT_QUEUE_CAPACITY,
+            policy: OverflowPolicy::default(),
+            on_failure: None,
+        }
+    }
+
+    /// 获取提交消息的句柄
+    pub fn handle(&self) -> WorkerHandle {
+        WorkerHandle {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// 关闭后台任务
+    ///
+    /// 不再接受新消息，并在 `deadline` 内继续发送队列中的消息。到达截止时间后，
+    /// 尚未发送的消息会通过 [`ShutdownReport::unsent`] 返回，正在发送的消息通过
+    /// [`ShutdownReport::in_flight`] 返回。
+    pub fn shutdown(mut self, deadline: Duration) -> ShutdownReport {
+        let deadline = Instant::now() + deadline;
+        let mut state = self.shared.lock();
+        state.close(Some(deadline));
+        self.shared.not_empty.notify_all();
+        self.shared.not_full.notify_all();
+
+        while !state.is_finished() {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if timeout.is_zero() {
+                break;
+            }
+            state = self
+                .shared
+                .finished
+                .wait_timeout(state, timeout)
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .0;
+        }
+
+        let finished = state.is_finished();
+        let report = state.report();
+        drop(state);
+
+        // 正在发送的请求可能超过截止时间，此时不再等待后台线程
+        if finished {
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+        report
+    }
+}
+
+impl Drop for BarkWorker {
+    fn drop(&mut self) {
+        self.shared.lock().close(None);
+        self.shared.not_empty.notify_all();
+        self.shared.not_full.notify_all();
+    }
+}
+
+impl WorkerHandle {
+    /// 提交一条消息
+    ///
+    /// 队列已满时按照 [`OverflowPolicy`] 处理：`Block` 会等待队列有空位，
+    /// `DropNewest` 返回 [`EnqueueError::Full`]，`DropOldest` 丢弃最早的消息。
+    /// 后台任务关闭后返回 [`EnqueueError::Closed`]。
+    pub fn enqueue(&self, message: BarkMessage) -> Result<(), EnqueueError> {
+        let mut state = self.shared.lock();
+        let mut message = message;
+        loop {
+            match state.push(message, self.shared.capacity, self.shared.policy) {
+                Push::Queued => {
+                    self.shared.not_empty.notify_one();
+                    return Ok(());
+                }
+                Push::Rejected(error) => return Err(error),
+                Push::Wait(pending) => {
+                    message = pending;
+                    state = self
+                        .shared
+                        .not_full
+                        .wait(state)
+                        .unwrap_or_else(|poisoned| poisoned.into_inner());
+                }
+            }
+        }
+    }
+
+    /// 等待队列中的消息发送完毕，最多等待 `timeout`
+    ///
+    /// 与 [`BarkWorker::shutdown`] 不同，后台任务不会被关闭，之后仍可以继续提交消息。
+    /// 队列已经清空且没有正在发送的消息时返回 `true`，超时或后台线程已经退出时返回 `false`。
+    pub fn flush(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.lock();
+        while !state.is_idle() {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if timeout.is_zero() || state.is_finished() {
+                return false;
+            }
+            state = self
+                .shared
+                .idle
+                .wait_timeout(state, timeout)
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .0;
+        }
+        true
+    }
+
+    /// 队列中等待发送的消息数量
+    pub fn len(&self) -> usize {
+        self.shared.lock().len()
+    }
+
+    /// 队列是否为空
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl BarkWorkerBuilder {
+    /// 设置队列容量，最小为 1
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// 设置队列已满时的处理方式
+    pub fn overflow_policy(mut self, policy: OverflowPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// 设置发送失败时的回调
+    ///
+    /// 后台发送的错误无法返回给调用方，可以在这里记录日志。回调在后台线程中执行。
+    pub fn on_failure<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&BarkMessage, &BarkError) + Send + Sync + 'static,
+    {
+        self.on_failure = Some(Arc::new(handler));
+        self
+    }
+
+    /// 启动后台线程
+    pub fn spawn(self) -> BarkWorker {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(QueueState::default()),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            finished: Condvar::new(),
+            idle: Condvar::new(),
+            capacity: self.capacity,
+            policy: self.policy,
+        });
+
+        let thread = {
+            let shared = shared.clone();
+            let client = self.client;
+            let on_failure = self.on_failure;
+            std::thread::Builder::new()
+                .name(WORKER_THREAD_NAME.to_string())
+                .spawn(move || run(&shared, &client, on_failure.as_ref()))
+                .expect("failed to spawn bark worker thread")
+        };
+
+        BarkWorker {
+            shared,
+            thread: Some(thread),
+        }
+    }
+}
+
+/// 后台线程的主循环
+fn run(shared: &Shared, client: &SyncBarkClient, on_failure: Option<&FailureHandler>) {
+    loop {
+        let message = {
+            let mut state = shared.lock();
+            loop {
+                match state.pop() {
+                    Pop::Message(message) => break message,
+                    Pop::Wait => {
+                        state = shared
+                            .not_empty
+                            .wait(state)
+                            .unwrap_or_else(|poisoned| poisoned.into_inner());
+                    }
+                    Pop::Exit => {
+                        state.finish();
+                        shared.finished.notify_all();
+                        shared.idle.notify_all();
+                        return;
+                    }
+                }
+            }
+        };
+        shared.not_full.notify_one();
+
+        let result = client.send(&message);
+        if let (Err(error), Some(on_failure)) = (&result, on_failure) {
+            on_failure(&message, error);
+        }
+        shared.lock().record(result.is_ok());
+        shared.idle.notify_all();
+    }
+}